@@ -0,0 +1,307 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env::var;
+use std::fmt;
+use std::sync::mpsc::Sender;
+use std::sync::OnceLock;
+
+use strum::{Display, EnumString};
+use tokio::runtime::Runtime;
+
+use crate::docker::{self, swarm};
+use crate::kubernetes;
+
+/// The platform the servers run on, set with `LAZYMC_BACKEND`
+#[derive(EnumString, Display, Clone, Copy, PartialEq, Eq, Debug)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+pub enum Backend {
+    /// Containers on a docker daemon
+    Docker,
+    /// Docker swarm services scaled between 0 and 1 replicas
+    Swarm,
+    /// Kubernetes deployments and statefulsets scaled between 0 and 1 replicas
+    Kubernetes,
+}
+
+/// An error from a backend
+#[derive(Debug)]
+pub enum Error {
+    /// A docker API call failed
+    Docker(bollard::errors::Error),
+    /// A kubernetes API call failed
+    Kubernetes(String),
+    /// A server or one of its dependencies did not become ready
+    NotReady { container: String, reason: String },
+    /// The servers this proxy instance manages could not be determined
+    Scope(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Docker(err) => {
+                // the cause of a connection failure, such as a refused connection or a bad certificate, is only in
+                // the source of the error
+                let mut message: String = err.to_string();
+                let mut source = std::error::Error::source(err);
+                while let Some(err) = source {
+                    let cause: String = err.to_string();
+                    if !message.contains(&cause) {
+                        message = format!("{}: {}", message, cause);
+                    }
+                    source = err.source();
+                }
+                write!(f, "{}", message)
+            }
+            Error::Kubernetes(err) | Error::Scope(err) => write!(f, "{}", err),
+            Error::NotReady { container, reason } => {
+                write!(f, "container {} {}", container, reason)
+            }
+        }
+    }
+}
+
+impl From<bollard::errors::Error> for Error {
+    fn from(err: bollard::errors::Error) -> Self {
+        Error::Docker(err)
+    }
+}
+
+/// The labels of a server with the label "lazymc.enabled=true"
+#[derive(Clone)]
+pub struct ContainerLabels {
+    /// The name of the container, service or workload
+    pub name: String,
+    pub labels: HashMap<String, String>,
+}
+
+/// A lifecycle event for a server with the label "lazymc.enabled=true"
+pub struct ContainerEvent {
+    /// The event action, e.g. "create", "start" or "connect", or "reconnect" once the event stream is back after it
+    /// ended
+    pub action: String,
+    /// The value of the "lazymc.group" label on the server
+    pub group: Option<String>,
+}
+
+/// The runtime backend API calls are made on
+static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+
+/// Get the runtime to block on backend API calls from synchronous code
+pub fn runtime() -> &'static Runtime {
+    RUNTIME.get_or_init(|| Runtime::new().expect("Failed to create the backend runtime"))
+}
+
+/// Get the backend, defaulting to docker
+pub fn get() -> Backend {
+    static BACKEND: OnceLock<Backend> = OnceLock::new();
+    *BACKEND.get_or_init(|| match var("LAZYMC_BACKEND") {
+        Ok(backend) if !backend.is_empty() => backend.parse().unwrap_or_else(|_| {
+            warn!(target: "lazymc-docker-proxy::backend", "Unknown LAZYMC_BACKEND `{}`, expected `docker`, `swarm` or `kubernetes`, using docker", backend);
+            Backend::Docker
+        }),
+        _ => Backend::Docker,
+    })
+}
+
+/// Get the log target of the backend, so servers are logged under the module which manages them
+fn target() -> &'static str {
+    match get() {
+        Backend::Docker | Backend::Swarm => "lazymc-docker-proxy::docker",
+        Backend::Kubernetes => "lazymc-docker-proxy::kubernetes",
+    }
+}
+
+/// Get the identifier of this proxy instance, set with `LAZYMC_PROXY_ID`, only servers whose "lazymc.proxy" label
+/// matches it are managed when it is set
+pub fn proxy_id() -> Option<&'static str> {
+    static PROXY_ID: OnceLock<Option<String>> = OnceLock::new();
+    PROXY_ID
+        .get_or_init(|| var("LAZYMC_PROXY_ID").ok().filter(|id| !id.is_empty()))
+        .as_deref()
+}
+
+/// Whether this proxy instance only manages the servers of its own Compose project or stack, set with
+/// `LAZYMC_PROXY_SCOPE=compose`
+pub fn compose_scoped() -> bool {
+    static COMPOSE_SCOPED: OnceLock<bool> = OnceLock::new();
+    *COMPOSE_SCOPED.get_or_init(|| match var("LAZYMC_PROXY_SCOPE") {
+        Ok(scope) if scope.eq_ignore_ascii_case("compose") => true,
+        Ok(scope) if !scope.is_empty() => {
+            warn!(target: "lazymc-docker-proxy::backend", "Unknown LAZYMC_PROXY_SCOPE `{}`, expected `compose`, managing servers of every project", scope);
+            false
+        }
+        _ => false,
+    })
+}
+
+/// Get the value of the "lazymc.start_order" label of a server, those without it are started first
+pub fn start_order(labels: Option<&HashMap<String, String>>, name: &str) -> i32 {
+    let Some(value) = labels.and_then(|labels| labels.get("lazymc.start_order")) else {
+        return 0;
+    };
+
+    value.parse().unwrap_or_else(|_| {
+        warn!(target: "lazymc-docker-proxy::backend", "Invalid lazymc.start_order `{}` on {}, expected a whole number", value, name);
+        0
+    })
+}
+
+/// Whether a container, service or workload is disabled with the label "lazymc.enabled=false", so it is never stopped
+/// along with its group
+pub fn disabled(labels: Option<&HashMap<String, String>>) -> bool {
+    labels
+        .and_then(|labels| labels.get("lazymc.enabled"))
+        .map(String::as_str)
+        == Some("false")
+}
+
+/// Split containers, services or workloads into stages which share a start order, in the order they are started
+pub fn stages<T>(items: Vec<T>, start_order: impl Fn(&T) -> i32) -> Vec<Vec<T>> {
+    let mut stages: BTreeMap<i32, Vec<T>> = BTreeMap::new();
+    for item in items {
+        stages.entry(start_order(&item)).or_default().push(item);
+    }
+    stages.into_values().collect()
+}
+
+/// Collect the results of concurrent backend calls, reporting the first failure once every call has finished
+pub fn first_error(results: Vec<Result<(), Error>>) -> Result<(), Error> {
+    results.into_iter().collect()
+}
+
+/// Unescape the labels of a server and point its "lazymc.server.address" label at its IP address, if it has one
+pub fn server_labels(
+    name: String,
+    labels: HashMap<String, String>,
+    ip_address: Option<String>,
+) -> ContainerLabels {
+    let mut labels: HashMap<String, String> = labels
+        .into_iter()
+        .map(|(key, value)| (key, value.replace("\\n", "\n")))
+        .collect();
+
+    // parse port from lazymc.server.address label
+    let port: Option<u16> = labels
+        .get("lazymc.server.address")
+        .and_then(|address| address.rsplit(':').next())
+        .and_then(|port_str| port_str.parse().ok());
+
+    let ip_address: Option<String> = ip_address.or_else(|| {
+        debug!(target: target(), "No IP address assigned to {}, it will be resolved once it has started", name);
+        None
+    });
+
+    // if we have a port and an IP address, add the resolved address to the labels
+    if let (Some(port), Some(ip_address)) = (port, ip_address) {
+        let address = format!("{}:{}", ip_address, port);
+        debug!(target: target(), "Resolved address: {}", address);
+        labels.insert("lazymc.server.address".to_string(), address);
+    }
+
+    ContainerLabels { name, labels }
+}
+
+/// Check that the API of the backend can be reached
+pub async fn ping() -> Result<(), Error> {
+    match get() {
+        Backend::Docker | Backend::Swarm => docker::ping().await,
+        Backend::Kubernetes => kubernetes::ping().await,
+    }
+}
+
+/// Start the servers of a group, returning whether any server was found for it
+pub async fn start(group: &str) -> Result<bool, Error> {
+    debug!(target: "lazymc-docker-proxy::backend", "Starting servers of group {} on {}...", group, get());
+    match get() {
+        Backend::Docker => docker::start(group).await,
+        Backend::Swarm => swarm::start(group).await,
+        Backend::Kubernetes => kubernetes::start(group).await,
+    }
+}
+
+/// Stop the servers of a group
+pub async fn stop(group: &str) -> Result<(), Error> {
+    match get() {
+        Backend::Docker => docker::stop(group).await,
+        Backend::Swarm => swarm::stop(group).await,
+        Backend::Kubernetes => kubernetes::stop(group).await,
+    }
+}
+
+/// Wait until the servers of a group are ready
+pub async fn wait_for_group(group: &str) -> Result<(), Error> {
+    match get() {
+        Backend::Docker => docker::wait_for_group(group).await,
+        Backend::Swarm => swarm::wait_for_group(group).await,
+        Backend::Kubernetes => kubernetes::wait_for_group(group).await,
+    }
+}
+
+/// Pause the servers of a group, only containers can be paused
+pub async fn pause(group: &str) -> Result<(), Error> {
+    match get() {
+        Backend::Docker => docker::pause(group).await,
+        backend => {
+            warn!(target: "lazymc-docker-proxy::backend", "Servers can not be paused on {}, the server of group {} keeps running", backend, group);
+            Ok(())
+        }
+    }
+}
+
+/// Resume the paused servers of a group
+pub async fn unpause(group: &str) -> Result<(), Error> {
+    match get() {
+        Backend::Docker => docker::unpause(group).await,
+        _ => Ok(()),
+    }
+}
+
+/// Follow the output of the running servers of a group since the given time, in seconds since the unix epoch, passing
+/// each line to `line` until the servers stop
+///
+/// The output of kubernetes pods is not followed.
+pub async fn follow_logs(group: &str, since: u64, line: impl FnMut(&str)) -> Result<(), Error> {
+    match get() {
+        Backend::Docker => docker::follow_logs(group, since, line).await,
+        Backend::Swarm => swarm::follow_logs(group, since, line).await,
+        Backend::Kubernetes => Ok(()),
+    }
+}
+
+/// Stop the servers of every group except the given ones
+pub async fn stop_all(except: &HashSet<String>) -> Result<(), Error> {
+    match get() {
+        Backend::Docker => docker::stop_all(except).await,
+        Backend::Swarm => swarm::stop_all(except).await,
+        Backend::Kubernetes => kubernetes::stop_all(except).await,
+    }
+}
+
+/// Get the name and state of each server of a group
+pub async fn get_states(group: &str) -> Result<Vec<(String, String)>, Error> {
+    match get() {
+        Backend::Docker => docker::get_states(group).await,
+        Backend::Swarm => swarm::get_states(group).await,
+        Backend::Kubernetes => kubernetes::get_states(group).await,
+    }
+}
+
+/// Get the labels of every server with the label "lazymc.enabled=true"
+pub async fn get_labels() -> Result<Vec<ContainerLabels>, Error> {
+    match get() {
+        Backend::Docker => docker::get_labels().await,
+        Backend::Swarm => swarm::get_labels().await,
+        Backend::Kubernetes => kubernetes::get_labels().await,
+    }
+}
+
+/// Watch for servers being created, removed or changing address
+///
+/// Returns when the event stream ends or the receiver is dropped.
+pub async fn watch(sender: Sender<ContainerEvent>) -> Result<(), Error> {
+    match get() {
+        Backend::Docker => docker::watch(sender).await,
+        Backend::Swarm => swarm::watch(sender).await,
+        Backend::Kubernetes => kubernetes::watch(sender).await,
+    }
+}
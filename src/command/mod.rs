@@ -1,25 +1,133 @@
-use std::{process, thread, time::Duration};
+use std::{
+    env, fs,
+    os::unix::process::parent_id,
+    process::{self, Command},
+    thread,
+    time::Duration,
+};
 
-use crate::docker;
+use crate::{backend, metrics};
 
-/// Run the command to start a group
-pub fn run(group: String) {
+/// How often the freeze watcher checks whether lazymc has frozen the command process
+const FREEZE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Run the command to start a group, optionally waiting for its containers to be healthy and pausing them
+/// while lazymc has frozen the server
+pub fn run(group: String, wait_for_health: bool, freeze: bool) {
+    metrics::report_through_log();
     info!(target: "lazymc-docker-proxy::command", "Received command to start group: {}", group);
     // Set a handler for SIGTERM
     let cloned_group = group.clone();
     ctrlc::set_handler(move || {
         info!(target: "lazymc-docker-proxy::command", "Received SIGTERM, stopping server...");
-        docker::stop(cloned_group.clone());
+        if let Err(err) = backend::runtime().block_on(backend::stop(&cloned_group)) {
+            error!(target: "lazymc-docker-proxy::command", "Failed to stop server for group {}: {}", cloned_group, err);
+            process::exit(1);
+        }
         process::exit(0);
     })
     .unwrap();
 
-    // Start the command
-    docker::start(group.clone());
+    // Start the servers, the proxy records the outcome in the health of the group from these log messages
+    match backend::runtime().block_on(backend::start(&group)) {
+        Ok(true) => {
+            info!(target: "lazymc-docker-proxy::command", "Started server containers of group {}", group)
+        }
+        Ok(false) => {
+            error!(target: "lazymc-docker-proxy::command", "No server found with the label lazymc.group={}", group)
+        }
+        Err(err) => {
+            error!(target: "lazymc-docker-proxy::command", "Failed to start server for group {}: {}", group, err);
+            failed(&group);
+        }
+    }
+
+    // Report a server which exits or becomes unhealthy to lazymc, instead of having it wait out its start timeout
+    if wait_for_health {
+        info!(target: "lazymc-docker-proxy::command", "Waiting for server containers of group {} to be healthy...", group);
+        if let Err(err) = backend::runtime().block_on(backend::wait_for_group(&group)) {
+            error!(target: "lazymc-docker-proxy::command", "Server for group {} failed to start: {}", group, err);
+            failed(&group);
+        }
+    }
+
+    // lazymc freezes a server by sending SIGSTOP to this process, which can not be handled by the process itself
+    if freeze {
+        let spawned = env::current_exe().and_then(|exe| {
+            Command::new(exe)
+                .args([
+                    "--watch-freeze",
+                    &process::id().to_string(),
+                    "--group",
+                    &group,
+                ])
+                .spawn()
+        });
+        if let Err(err) = spawned {
+            error!(target: "lazymc-docker-proxy::command", "Failed to watch for lazymc freezing group {}, the server will not be paused: {}", group, err);
+        }
+    }
 
     // Wait for SIGTERM
+    let parent: u32 = parent_id();
     loop {
         trace!(target: "lazymc-docker-proxy::command", "Waiting for SIGTERM...");
         thread::sleep(Duration::from_secs(1));
+
+        // Exit if the lazymc process was terminated by the proxy, the server is handed over to its replacement
+        if parent_id() != parent {
+            info!(target: "lazymc-docker-proxy::command", "lazymc process for group {} exited, leaving server running...", group);
+            process::exit(0);
+        }
+    }
+}
+
+/// Stop the containers of a group which failed to start and exit, so lazymc sees the failure
+fn failed(group: &str) -> ! {
+    if let Err(err) = backend::runtime().block_on(backend::stop(group)) {
+        error!(target: "lazymc-docker-proxy::command", "Failed to stop server for group {}: {}", group, err);
+    }
+    process::exit(1);
+}
+
+/// Pause the containers of a group while lazymc has frozen the command process with the given pid, and unpause
+/// them once it is resumed
+///
+/// Runs as a child of the command process, and exits once the command process exits.
+pub fn watch_freeze(group: String, pid: u32) {
+    metrics::report_through_log();
+    let mut frozen: bool = false;
+    loop {
+        thread::sleep(FREEZE_POLL_INTERVAL);
+        if parent_id() != pid {
+            return;
+        }
+
+        // the state follows the executable name in parentheses, `T` when the process was sent SIGSTOP
+        let stopped: bool = fs::read_to_string(format!("/proc/{}/stat", pid))
+            .ok()
+            .and_then(|stat| {
+                stat.rsplit_once(')')
+                    .and_then(|(_, fields)| fields.trim_start().chars().next())
+            })
+            == Some('T');
+        if stopped == frozen {
+            continue;
+        }
+        frozen = stopped;
+
+        let result = match frozen {
+            true => {
+                info!(target: "lazymc-docker-proxy::command", "Server frozen by lazymc, pausing containers for group {}...", group);
+                backend::runtime().block_on(backend::pause(&group))
+            }
+            false => {
+                info!(target: "lazymc-docker-proxy::command", "Server unfrozen by lazymc, unpausing containers for group {}...", group);
+                backend::runtime().block_on(backend::unpause(&group))
+            }
+        };
+        if let Err(err) = result {
+            error!(target: "lazymc-docker-proxy::command", "Failed to {} containers for group {}: {}", if frozen { "pause" } else { "unpause" }, group, err);
+        }
     }
 }
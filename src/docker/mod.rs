@@ -0,0 +1,1322 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::env::var;
+use std::fs;
+use std::future::Future;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use bollard::container::LogOutput;
+use bollard::models::{
+    ContainerStateStatusEnum, ContainerSummary, ContainerSummaryStateEnum, EventMessageTypeEnum,
+    HealthStatusEnum,
+};
+use bollard::query_parameters::{
+    EventsOptions, InspectContainerOptions, KillContainerOptions, ListContainersOptions,
+    LogsOptions, StartContainerOptions, StopContainerOptions,
+};
+use bollard::{Docker, API_DEFAULT_VERSION};
+use futures::{future, stream, Stream, StreamExt};
+use log::error;
+
+use crate::backend::{
+    self, compose_scoped, disabled, first_error, proxy_id, server_labels, stages, start_order,
+    Backend, ContainerEvent, ContainerLabels, Error,
+};
+use crate::entrypoint::config::file;
+use crate::{metrics, rcon};
+
+pub mod swarm;
+
+/// How long to wait for the containers of a start stage to become ready before giving up
+const STAGE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// How long a stop request may take on top of the stop timeout of the container
+const STOP_REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// How long to wait for a container to exit after stopping it through RCON, unless "lazymc.stop.timeout" is set
+const RCON_STOP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Timeout in seconds for a request to the docker API, unless set with `LAZYMC_DOCKER_TIMEOUT`
+const DEFAULT_TIMEOUT: u64 = 120;
+
+/// Timeout for checking whether the docker API can be reached
+const PING_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often the state of a container is checked while waiting for it to become ready
+const READY_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The name of the docker host which is found automatically or set with `LAZYMC_DOCKER_SOCKET` or `DOCKER_HOST`
+const DEFAULT_HOST: &str = "default";
+
+/// A docker daemon servers run on
+struct Endpoint {
+    /// The name of the docker host, as used by the "lazymc.host" label
+    name: String,
+    docker: Docker,
+    /// The address of the machine of a remote daemon, whose servers are reached through their published ports
+    remote: Option<String>,
+    /// Whether the docker API is served by Podman, known once it has been asked for its version
+    podman: OnceLock<bool>,
+}
+
+/// The docker hosts, the default one first
+static ENDPOINTS: OnceLock<Vec<Endpoint>> = OnceLock::new();
+
+/// Get the docker hosts, connecting to the default docker daemon and those in the `[hosts]` table of the
+/// configuration file on first use
+fn endpoints() -> Result<&'static [Endpoint], Error> {
+    if let Some(endpoints) = ENDPOINTS.get() {
+        return Ok(endpoints);
+    }
+
+    let timeout: u64 = timeout();
+    let docker: Docker = match (socket(), remote_host()) {
+        (Some(socket), _) => {
+            debug!(target: "lazymc-docker-proxy::docker", "Connecting to the docker API at {}", socket);
+            Docker::connect_with_socket(&socket, timeout, API_DEFAULT_VERSION)
+        }
+        (None, Some(host)) => connect(
+            &host,
+            var("DOCKER_TLS_VERIFY").is_ok_and(|tls_verify| !tls_verify.is_empty()),
+            var("DOCKER_CERT_PATH")
+                .ok()
+                .filter(|cert_path| !cert_path.is_empty()),
+            timeout,
+        ),
+        // no socket was found, so report the default one as missing
+        (None, None) => Docker::connect_with_local_defaults(),
+    }
+    .map_err(count_error(""))?;
+    let mut endpoints: Vec<Endpoint> = vec![Endpoint {
+        name: DEFAULT_HOST.to_string(),
+        docker,
+        remote: remote_host().map(|host| machine(&host)),
+        podman: OnceLock::new(),
+    }];
+
+    let hosts: BTreeMap<String, file::Host> = file::hosts().unwrap_or_else(|err| {
+        error!(target: "lazymc-docker-proxy::docker", "Failed to get the docker hosts from the configuration file: {}", err);
+        BTreeMap::new()
+    });
+    for (name, host) in hosts {
+        if name == DEFAULT_HOST {
+            warn!(target: "lazymc-docker-proxy::docker", "Ignoring the docker host `{}` of the configuration file, the name is used by the default docker host", name);
+            continue;
+        }
+        // a host which can not be connected to should not keep the servers of the other hosts from being managed
+        let docker: Docker = match connect(&host.url, host.tls_verify, host.cert_path, timeout) {
+            Ok(docker) => docker,
+            Err(err) => {
+                error!(target: "lazymc-docker-proxy::docker", "Failed to connect to docker host {}, its servers will not be managed: {}", name, err);
+                metrics::report_docker_error("", &err);
+                continue;
+            }
+        };
+        let remote: Option<String> = match host.url.starts_with("unix://") {
+            true => None,
+            false => Some(host.address.unwrap_or_else(|| machine(&host.url))),
+        };
+        endpoints.push(Endpoint {
+            name,
+            docker,
+            remote,
+            podman: OnceLock::new(),
+        });
+    }
+    Ok(ENDPOINTS.get_or_init(|| endpoints))
+}
+
+/// Get the client of the default docker host
+fn client() -> Result<&'static Docker, Error> {
+    Ok(&endpoints()?[0].docker)
+}
+
+/// Make a docker API call on every docker host concurrently, skipping the hosts it failed on
+///
+/// The call only fails if it failed on every docker host, with the first failure.
+async fn on_every_host<T, F: Future<Output = Result<T, Error>>>(
+    call: impl Fn(&'static Endpoint) -> F,
+) -> Result<Vec<T>, Error> {
+    let endpoints: &'static [Endpoint] = endpoints()?;
+    let results: Vec<Result<T, Error>> = future::join_all(endpoints.iter().map(call)).await;
+
+    let mut values: Vec<T> = Vec::new();
+    let mut failure: Option<Error> = None;
+    for (endpoint, result) in endpoints.iter().zip(results) {
+        match result {
+            Ok(value) => values.push(value),
+            Err(err) => {
+                if endpoints.len() > 1 {
+                    warn!(target: "lazymc-docker-proxy::docker", "Skipping docker host {}: {}", endpoint.name, err);
+                }
+                failure.get_or_insert(err);
+            }
+        }
+    }
+    match failure {
+        Some(err) if values.is_empty() => Err(err),
+        _ => Ok(values),
+    }
+}
+
+/// Get the docker host the servers of a group run on, which is the first host a server of the group is found on
+/// that it is not bound away from by its "lazymc.host" label
+///
+/// Hosts which can not be listed are skipped, and `None` is returned if no server of the group is found.
+async fn group_endpoint(group: &str) -> Result<Option<&'static Endpoint>, Error> {
+    let endpoints: &'static [Endpoint] = endpoints()?;
+    if let [endpoint] = endpoints {
+        return Ok(Some(endpoint));
+    }
+
+    let filters: HashMap<String, Vec<String>> = HashMap::from([(
+        "label".to_string(),
+        vec![
+            "lazymc.enabled=true".to_string(),
+            format!("lazymc.group={}", group),
+        ],
+    )]);
+    for endpoint in endpoints {
+        match list(endpoint, filters.clone(), group).await {
+            Ok(servers) if !servers.is_empty() => return Ok(Some(endpoint)),
+            Ok(_) => {}
+            Err(err) => {
+                warn!(target: "lazymc-docker-proxy::docker", "Skipping docker host {} while looking for the server of group {}: {}", endpoint.name, group, err)
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Make a docker API call for a group on its docker host, or on every docker host if its server is not found so
+/// containers left behind by a removed server are still handled
+async fn on_group_host<T, F: Future<Output = Result<T, Error>>>(
+    group: &str,
+    call: impl Fn(&'static Endpoint) -> F,
+) -> Result<Vec<T>, Error> {
+    match group_endpoint(group).await? {
+        Some(endpoint) => call(endpoint).await.map(|value| vec![value]),
+        None => on_every_host(call).await,
+    }
+}
+
+/// Get the timeout in seconds for a request to the docker API
+fn timeout() -> u64 {
+    match var("LAZYMC_DOCKER_TIMEOUT") {
+        Ok(timeout) if !timeout.is_empty() => timeout.parse().unwrap_or_else(|_| {
+            warn!(target: "lazymc-docker-proxy::docker", "Invalid LAZYMC_DOCKER_TIMEOUT `{}`, expected a number of seconds, using {}", timeout, DEFAULT_TIMEOUT);
+            DEFAULT_TIMEOUT
+        }),
+        _ => DEFAULT_TIMEOUT,
+    }
+}
+
+/// Get the address of a remote docker daemon from `DOCKER_HOST`, unless a socket is used instead
+fn remote_host() -> Option<String> {
+    if var("LAZYMC_DOCKER_SOCKET").is_ok_and(|socket| !socket.is_empty()) {
+        return None;
+    }
+    var("DOCKER_HOST")
+        .ok()
+        .filter(|host| !host.is_empty() && !host.starts_with("unix://"))
+}
+
+/// Get the machine of a docker API address, such as `192.168.1.20` for `tcp://192.168.1.20:2376`
+fn machine(host: &str) -> String {
+    let authority: &str = host
+        .split_once("://")
+        .map_or(host, |(_, rest)| rest)
+        .split('/')
+        .next()
+        .unwrap_or_default();
+    authority
+        .rsplit_once(':')
+        .map_or(authority, |(machine, _)| machine)
+        .to_string()
+}
+
+/// Connect to a docker daemon through a `unix://` socket or over TCP, authenticating with the TLS client
+/// certificate in the given directory, or `~/.docker`, when TLS is verified or the address is `https://`
+fn connect(
+    host: &str,
+    tls_verify: bool,
+    cert_path: Option<String>,
+    timeout: u64,
+) -> Result<Docker, bollard::errors::Error> {
+    let tls: bool = host.starts_with("https://") || tls_verify;
+
+    match host.split_once("://").map(|(scheme, _)| scheme) {
+        Some("unix") => {
+            debug!(target: "lazymc-docker-proxy::docker", "Connecting to the docker API at {}", host);
+            Docker::connect_with_socket(host, timeout, API_DEFAULT_VERSION)
+        }
+        Some("tcp" | "http" | "https") if tls => {
+            let cert_path: PathBuf = cert_path
+                .map(PathBuf::from)
+                .unwrap_or_else(|| Path::new(&var("HOME").unwrap_or_default()).join(".docker"));
+            info!(target: "lazymc-docker-proxy::docker", "Connecting to the docker API at {} with the TLS client certificate in {}", host, cert_path.display());
+            Docker::connect_with_ssl(
+                host,
+                &cert_path.join("key.pem"),
+                &cert_path.join("cert.pem"),
+                &cert_path.join("ca.pem"),
+                timeout,
+                API_DEFAULT_VERSION,
+            )
+        }
+        Some("tcp" | "http") => {
+            info!(target: "lazymc-docker-proxy::docker", "Connecting to the docker API at {} without TLS", host);
+            Docker::connect_with_http(host, timeout, API_DEFAULT_VERSION)
+        }
+        // ssh is not supported directly, a socket forwarded by ssh is used through tcp:// instead
+        _ => Err(bollard::errors::Error::UnsupportedURISchemeError {
+            uri: host.to_string(),
+        }),
+    }
+}
+
+/// Find the socket of the docker API, preferring `LAZYMC_DOCKER_SOCKET` and a `unix://` `DOCKER_HOST`, then the
+/// first socket found of rootful docker, rootless docker, rootless Podman and rootful Podman
+fn socket() -> Option<String> {
+    if let Ok(socket) = var("LAZYMC_DOCKER_SOCKET") {
+        if !socket.is_empty() {
+            return Some(socket);
+        }
+    }
+    if let Ok(host) = var("DOCKER_HOST") {
+        if !host.is_empty() {
+            return host.starts_with("unix://").then_some(host);
+        }
+    }
+
+    // the runtime directory of the user, where rootless docker and Podman put their sockets
+    let runtime_dir: Option<String> = var("XDG_RUNTIME_DIR")
+        .ok()
+        .filter(|runtime_dir| !runtime_dir.is_empty())
+        .or_else(|| {
+            fs::metadata("/proc/self")
+                .ok()
+                .map(|metadata| format!("/run/user/{}", metadata.uid()))
+        });
+
+    let mut sockets: Vec<String> = vec!["/var/run/docker.sock".to_string()];
+    if let Some(runtime_dir) = runtime_dir {
+        sockets.push(format!("{}/docker.sock", runtime_dir));
+        sockets.push(format!("{}/podman/podman.sock", runtime_dir));
+    }
+    sockets.push("/run/podman/podman.sock".to_string());
+
+    sockets
+        .into_iter()
+        .find(|socket| Path::new(socket).exists())
+}
+
+/// Whether the docker API of a host is served by Podman, whose Docker-compatible API differs in some filters and
+/// fields
+async fn is_podman(endpoint: &Endpoint) -> bool {
+    if let Some(podman) = endpoint.podman.get() {
+        return *podman;
+    }
+
+    // ask again next time if the version is not known yet
+    let Ok(version) = endpoint.docker.version().await else {
+        return false;
+    };
+    let podman: bool = version
+        .components
+        .unwrap_or_default()
+        .iter()
+        .any(|component| component.name.to_lowercase().starts_with("podman"));
+    if podman {
+        info!(target: "lazymc-docker-proxy::docker", "Using the Docker-compatible API of Podman {} on docker host {}", version.version.unwrap_or_default(), endpoint.name);
+    }
+    *endpoint.podman.get_or_init(|| podman)
+}
+
+/// Count a failed docker API call, the group is empty if the call was not made for a single group
+fn count_error(group: &str) -> impl FnOnce(bollard::errors::Error) -> Error + '_ {
+    move |err| {
+        metrics::report_docker_error(group, &err);
+        Error::Docker(err)
+    }
+}
+
+/// Get the name of a container
+fn name(container: &ContainerSummary) -> String {
+    container
+        .names
+        .as_ref()
+        .and_then(|names| names.first())
+        .map(|name| name.trim_start_matches('/').to_string())
+        .unwrap_or_default()
+}
+
+/// Get the IP address of a container from its ipam config, falling back to the address currently assigned
+/// to the container, which is only known while it is running
+fn ip_address(container: &ContainerSummary) -> Option<String> {
+    let networks = container
+        .network_settings
+        .as_ref()
+        .and_then(|network_settings| network_settings.networks.as_ref())?;
+    networks
+        .values()
+        .find_map(|network| network.ipam_config.as_ref())
+        .and_then(|ipam_config| ipam_config.ipv4_address.clone())
+        .or_else(|| {
+            networks
+                .values()
+                .filter_map(|network| network.ip_address.clone())
+                .find(|ip_address| !ip_address.is_empty())
+        })
+}
+
+/// Get the IP address of a container, inspecting a running container whose networks are missing from the list,
+/// as Podman leaves them out for some network modes
+async fn resolve_ip_address(docker: &Docker, container: &ContainerSummary) -> Option<String> {
+    if let Some(ip_address) = ip_address(container) {
+        return Some(ip_address);
+    }
+    if container.state != Some(ContainerSummaryStateEnum::RUNNING) {
+        return None;
+    }
+
+    docker
+        .inspect_container(container.id.as_deref()?, None::<InspectContainerOptions>)
+        .await
+        .ok()?
+        .network_settings?
+        .networks?
+        .into_values()
+        .filter_map(|network| network.ip_address)
+        .find(|ip_address| !ip_address.is_empty())
+}
+
+/// Get the port of a container published on the machine of a remote docker daemon, from the ports of a running
+/// container or the port bindings of a stopped one
+async fn published_port(docker: &Docker, container: &ContainerSummary, port: u16) -> Option<u16> {
+    let published: Option<u16> = container
+        .ports
+        .iter()
+        .flatten()
+        .filter(|summary| summary.private_port == port)
+        .find_map(|summary| summary.public_port);
+    if published.is_some() {
+        return published;
+    }
+
+    docker
+        .inspect_container(container.id.as_deref()?, None::<InspectContainerOptions>)
+        .await
+        .ok()?
+        .host_config?
+        .port_bindings?
+        .remove(&format!("{}/tcp", port))??
+        .into_iter()
+        .find_map(|binding| binding.host_port?.parse().ok())
+}
+
+/// Get the docker host a container is bound to by its "lazymc.host" label, or that of its group in the
+/// configuration file
+fn bound_host(container: &ContainerSummary) -> Option<String> {
+    file::merge(container.labels.clone().unwrap_or_default())
+        .ok()?
+        .labels
+        .remove("lazymc.host")
+}
+
+/// Warn once about a container bound to a docker host which does not exist, as it is then never managed
+fn warn_unknown_host(container: &ContainerSummary, host: &str) {
+    static WARNED: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+    let known: bool = ENDPOINTS
+        .get()
+        .is_some_and(|endpoints| endpoints.iter().any(|endpoint| endpoint.name == host));
+    if known
+        || !WARNED
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .insert(name(container))
+    {
+        return;
+    }
+    warn!(target: "lazymc-docker-proxy::docker", "Container {} is bound to the unknown docker host {} by lazymc.host, it will not be managed", name(container), host);
+}
+
+/// Get the label filters which limit the containers or services this proxy instance manages, resolved on first use
+///
+/// With `LAZYMC_PROXY_SCOPE=compose` the Compose project, or the stack on swarm, is read from the labels of the
+/// container the proxy runs in.
+async fn scope() -> Result<&'static [String], Error> {
+    static SCOPE: OnceLock<Vec<String>> = OnceLock::new();
+    if let Some(scope) = SCOPE.get() {
+        return Ok(scope);
+    }
+
+    let mut scope: Vec<String> = Vec::new();
+    if let Some(id) = proxy_id() {
+        scope.push(format!("lazymc.proxy={}", id));
+    }
+    if compose_scoped() {
+        let key: &str = match backend::get() {
+            Backend::Swarm => "com.docker.stack.namespace",
+            _ => "com.docker.compose.project",
+        };
+        scope.push(format!("{}={}", key, own_label(key).await?));
+    }
+
+    Ok(SCOPE.get_or_init(|| {
+        if !scope.is_empty() {
+            info!(target: "lazymc-docker-proxy::docker", "Managing only servers with the labels {}", scope.join(", "));
+        }
+        scope
+    }))
+}
+
+/// Get a label of the container the proxy runs in, found by its hostname on the default docker host
+async fn own_label(key: &str) -> Result<String, Error> {
+    let hostname: String = var("HOSTNAME")
+        .ok()
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())
+        .map(|hostname| hostname.trim().to_string())
+        .unwrap_or_default();
+
+    let container = client()?
+        .inspect_container(&hostname, None::<InspectContainerOptions>)
+        .await
+        .map_err(|err| {
+            Error::Scope(format!(
+                "Failed to find the container of the proxy by its hostname `{}`, set LAZYMC_PROXY_ID instead of LAZYMC_PROXY_SCOPE: {}",
+                hostname,
+                Error::Docker(err)
+            ))
+        })?;
+    container
+        .config
+        .and_then(|config| config.labels)
+        .and_then(|mut labels| labels.remove(key))
+        .ok_or_else(|| {
+            Error::Scope(format!(
+                "The container of the proxy has no {} label, it must be deployed with Compose to use LAZYMC_PROXY_SCOPE=compose",
+                key
+            ))
+        })
+}
+
+/// List all containers of a docker host, including stopped ones, matching the given filters
+///
+/// Containers bound to another docker host or outside the scope of this proxy instance are left out.
+async fn list(
+    endpoint: &Endpoint,
+    mut filters: HashMap<String, Vec<String>>,
+    group: &str,
+) -> Result<Vec<ContainerSummary>, Error> {
+    let docker: &Docker = &endpoint.docker;
+    filters
+        .entry("label".to_string())
+        .or_default()
+        .extend(scope().await?.iter().cloned());
+
+    // Podman applies some filters differently from docker, so only labels are filtered by its API and the
+    // containers it returns are checked against every filter here
+    let podman: bool = is_podman(endpoint).await;
+    let api_filters: HashMap<String, Vec<String>> = match podman {
+        true => filters
+            .iter()
+            .filter(|(key, _)| key.as_str() == "label")
+            .map(|(key, values)| (key.clone(), values.clone()))
+            .collect(),
+        false => filters.clone(),
+    };
+
+    let containers: Vec<ContainerSummary> = docker
+        .list_containers(Some(ListContainersOptions {
+            all: true,
+            filters: Some(api_filters),
+            ..Default::default()
+        }))
+        .await
+        .map_err(count_error(group))?;
+
+    Ok(containers
+        .into_iter()
+        .filter(|container| !podman || matches_filters(container, &filters))
+        .filter(|container| match bound_host(container) {
+            Some(host) if host != endpoint.name => {
+                debug!(target: "lazymc-docker-proxy::docker", "Skipping container {} on docker host {}, it is bound to docker host {}", name(container), endpoint.name, host);
+                warn_unknown_host(container, &host);
+                false
+            }
+            _ => true,
+        })
+        .collect())
+}
+
+/// Whether labels match every label filter, given as `key=value` or just `key`
+fn has_labels(labels: &HashMap<String, String>, filters: &[String]) -> bool {
+    filters.iter().all(|label| match label.split_once('=') {
+        Some((key, value)) => labels.get(key).map(String::as_str) == Some(value),
+        None => labels.contains_key(label),
+    })
+}
+
+/// Whether a container matches every label and one of the statuses of the given filters
+fn matches_filters(container: &ContainerSummary, filters: &HashMap<String, Vec<String>>) -> bool {
+    let labels = container.labels.clone().unwrap_or_default();
+    filters.iter().all(|(key, values)| match key.as_str() {
+        "label" => has_labels(&labels, values),
+        "status" => container
+            .state
+            .is_some_and(|state| values.contains(&state.to_string())),
+        _ => true,
+    })
+}
+
+/// Get the start order of a container
+fn container_start_order(container: &ContainerSummary) -> i32 {
+    start_order(
+        container.labels.as_ref(),
+        &format!("container {}", name(container)),
+    )
+}
+
+/// Stop the given containers in the reverse of their start order, the containers of each stage concurrently
+async fn stop_containers(
+    docker: &Docker,
+    containers: Vec<ContainerSummary>,
+    group: &str,
+) -> Result<(), Error> {
+    let mut result: Result<(), Error> = Ok(());
+    for stage in stages(containers, container_start_order).into_iter().rev() {
+        let results: Vec<Result<(), Error>> = future::join_all(
+            stage
+                .iter()
+                .map(|container| stop_container(docker, container, group)),
+        )
+        .await;
+
+        // keep stopping the remaining stages, a container which failed to stop should not keep the others running
+        result = result.and(first_error(results));
+    }
+    result
+}
+
+/// Stop a container with the signal and timeout from its "lazymc.stop.signal" and "lazymc.stop.timeout" labels,
+/// killing it if the stop request timed out
+async fn stop_container(
+    docker: &Docker,
+    container: &ContainerSummary,
+    group: &str,
+) -> Result<(), Error> {
+    let name: String = name(container);
+    let id: &str = container.id.as_deref().unwrap_or_default();
+    let labels = container.labels.as_ref();
+    let signal: Option<String> =
+        labels.and_then(|labels| labels.get("lazymc.stop.signal").cloned());
+    let timeout: Option<i32> = labels
+        .and_then(|labels| labels.get("lazymc.stop.timeout"))
+        .and_then(|timeout| match timeout.parse::<i32>() {
+            Ok(timeout) if timeout >= 0 => Some(timeout),
+            _ => {
+                warn!(target: "lazymc-docker-proxy::docker", "Invalid lazymc.stop.timeout `{}` on container {}, expected a whole number of seconds", timeout, name);
+                None
+            }
+        });
+
+    // a paused server can not handle the stop signal, so it is resumed first
+    if container.state == Some(ContainerSummaryStateEnum::PAUSED) {
+        unpause_container(docker, container, group).await?;
+    }
+
+    let enabled = |label: &str| {
+        labels
+            .and_then(|labels| labels.get(label))
+            .map(String::as_str)
+            == Some("true")
+    };
+    if enabled("lazymc.rcon.enabled") {
+        let rcon_timeout: Duration = timeout.map_or(RCON_STOP_TIMEOUT, |timeout| {
+            Duration::from_secs(timeout as u64)
+        });
+        if stop_with_rcon(docker, container, rcon_timeout).await {
+            return Ok(());
+        }
+    }
+
+    info!(target: "lazymc-docker-proxy::docker", "Stopping container {} with {} and a {} timeout...", name, signal.as_deref().unwrap_or("its stop signal"), timeout.map_or("default".to_string(), |timeout| format!("{} second", timeout)));
+
+    // docker kills the container itself once the timeout elapses, the request must outlive it
+    let docker: Docker = docker.clone().with_timeout(
+        STOP_REQUEST_TIMEOUT + Duration::from_secs(timeout.unwrap_or_default() as u64),
+    );
+    let stopped = docker
+        .stop_container(id, Some(StopContainerOptions { signal, t: timeout }))
+        .await;
+
+    match stopped {
+        Ok(()) => {}
+        // only a container which could not be stopped in time is killed, a rejected request such as an unknown
+        // signal is reported instead
+        Err(err @ bollard::errors::Error::RequestTimeoutError) => {
+            warn!(target: "lazymc-docker-proxy::docker", "Timed out stopping container {}, killing it: {}", name, err);
+            metrics::report_docker_error(group, &err);
+            return match docker
+                .kill_container(id, Some(KillContainerOptions::default()))
+                .await
+            {
+                Ok(()) => {
+                    warn!(target: "lazymc-docker-proxy::docker", "Container {} was killed", name);
+                    Ok(())
+                }
+                Err(err) => {
+                    error!(target: "lazymc-docker-proxy::docker", "Error killing container {}: {}", name, err);
+                    Err(count_error(group)(err))
+                }
+            };
+        }
+        Err(err) => {
+            error!(target: "lazymc-docker-proxy::docker", "Error stopping container {}: {}", name, err);
+            return Err(count_error(group)(err));
+        }
+    }
+
+    // a container which did not stop within the timeout is sent SIGKILL, and exits with 128 + 9
+    let exit_code: Option<i64> = docker
+        .inspect_container(id, None::<InspectContainerOptions>)
+        .await
+        .ok()
+        .and_then(|container| container.state)
+        .and_then(|state| state.exit_code);
+    match exit_code {
+        Some(137) => {
+            warn!(target: "lazymc-docker-proxy::docker", "Container {} did not stop in time and was killed", name)
+        }
+        _ => info!(target: "lazymc-docker-proxy::docker", "Container {} stopped gracefully", name),
+    }
+    Ok(())
+}
+
+/// Save the world and stop the server in a container through RCON, returning whether the container then exited on its own
+///
+/// The address and password are taken from the "lazymc.rcon.address" and "lazymc.rcon.password" labels, falling back
+/// to the IP address of the container and the `RCON_PORT` and `RCON_PASSWORD` environment variables of the container.
+async fn stop_with_rcon(docker: &Docker, container: &ContainerSummary, timeout: Duration) -> bool {
+    let name: String = name(container);
+    let id: &str = container.id.as_deref().unwrap_or_default();
+    let labels: HashMap<String, String> = container.labels.clone().unwrap_or_default();
+
+    // the environment of the container is only needed when the labels are not set
+    let mut env: HashMap<String, String> = HashMap::new();
+    if !labels.contains_key("lazymc.rcon.password") || !labels.contains_key("lazymc.rcon.address") {
+        env = docker
+            .inspect_container(id, None::<InspectContainerOptions>)
+            .await
+            .ok()
+            .and_then(|container| container.config)
+            .and_then(|config| config.env)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|variable| {
+                variable
+                    .split_once('=')
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+            })
+            .collect();
+    }
+
+    let Some(password) = labels
+        .get("lazymc.rcon.password")
+        .or(env.get("RCON_PASSWORD"))
+    else {
+        warn!(target: "lazymc-docker-proxy::docker", "No RCON password for container {}, set lazymc.rcon.password or RCON_PASSWORD, falling back to docker stop", name);
+        return false;
+    };
+    let Some(address) = labels.get("lazymc.rcon.address").cloned().or_else(|| {
+        let port: &str = env.get("RCON_PORT").map_or("25575", String::as_str);
+        ip_address(container).map(|ip_address| format!("{}:{}", ip_address, port))
+    }) else {
+        warn!(target: "lazymc-docker-proxy::docker", "No RCON address for container {}, set lazymc.rcon.address, falling back to docker stop", name);
+        return false;
+    };
+
+    info!(target: "lazymc-docker-proxy::docker", "Saving and stopping container {} using RCON at {}...", name, address);
+    if let Err(err) = rcon::save_and_stop(&address, password).await {
+        warn!(target: "lazymc-docker-proxy::docker", "Failed to stop container {} using RCON, falling back to docker stop: {}", name, err);
+        return false;
+    }
+
+    let deadline: Instant = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        let running: bool = docker
+            .inspect_container(id, None::<InspectContainerOptions>)
+            .await
+            .ok()
+            .and_then(|container| container.state)
+            .and_then(|state| state.running)
+            .unwrap_or(false);
+        if !running {
+            info!(target: "lazymc-docker-proxy::docker", "Container {} stopped gracefully using RCON", name);
+            return true;
+        }
+        tokio::time::sleep(READY_POLL_INTERVAL).await;
+    }
+
+    warn!(target: "lazymc-docker-proxy::docker", "Container {} did not exit within {} seconds of the RCON stop command, falling back to docker stop", name, timeout.as_secs());
+    false
+}
+
+/// Resume a paused container
+async fn unpause_container(
+    docker: &Docker,
+    container: &ContainerSummary,
+    group: &str,
+) -> Result<(), Error> {
+    info!(target: "lazymc-docker-proxy::docker", "Unpausing container: {}", name(container));
+    docker
+        .unpause_container(container.id.as_deref().unwrap_or_default())
+        .await
+        .map_err(|err| {
+            error!(target: "lazymc-docker-proxy::docker", "Error unpausing container {}: {}", name(container), err);
+            count_error(group)(err)
+        })
+}
+
+/// Pause the running containers with the label "lazymc.group=group", freezing the server without stopping it
+pub async fn pause(group: &str) -> Result<(), Error> {
+    on_group_host(group, |endpoint| pause_on(endpoint, group))
+        .await
+        .map(|_| ())
+}
+
+/// Pause the running containers of a group on a docker host
+async fn pause_on(endpoint: &Endpoint, group: &str) -> Result<(), Error> {
+    let docker: &Docker = &endpoint.docker;
+    let mut list_container_filters: HashMap<String, Vec<String>> =
+        HashMap::<String, Vec<String>>::new();
+
+    list_container_filters.insert("status".to_string(), vec!["running".to_string()]);
+    list_container_filters.insert("label".to_string(), vec![format!("lazymc.group={}", group)]);
+
+    let containers: Vec<ContainerSummary> = list(endpoint, list_container_filters, group).await?;
+    first_error(
+        future::join_all(containers.iter().map(|container| async move {
+            info!(target: "lazymc-docker-proxy::docker", "Pausing container: {}", name(container));
+            docker
+                .pause_container(container.id.as_deref().unwrap_or_default())
+                .await
+                .map_err(|err| {
+                    error!(target: "lazymc-docker-proxy::docker", "Error pausing container {}: {}", name(container), err);
+                    count_error(group)(err)
+                })
+        }))
+        .await,
+    )
+}
+
+/// Resume the paused containers with the label "lazymc.group=group"
+pub async fn unpause(group: &str) -> Result<(), Error> {
+    on_group_host(group, |endpoint| unpause_on(endpoint, group))
+        .await
+        .map(|_| ())
+}
+
+/// Resume the paused containers of a group on a docker host
+async fn unpause_on(endpoint: &Endpoint, group: &str) -> Result<(), Error> {
+    let docker: &Docker = &endpoint.docker;
+    let mut list_container_filters: HashMap<String, Vec<String>> =
+        HashMap::<String, Vec<String>>::new();
+
+    list_container_filters.insert("status".to_string(), vec!["paused".to_string()]);
+    list_container_filters.insert("label".to_string(), vec![format!("lazymc.group={}", group)]);
+
+    let containers: Vec<ContainerSummary> = list(endpoint, list_container_filters, group).await?;
+    first_error(
+        future::join_all(
+            containers
+                .iter()
+                .map(|container| unpause_container(docker, container, group)),
+        )
+        .await,
+    )
+}
+
+/// Check that the docker API of every docker host can be reached
+pub async fn ping() -> Result<(), Error> {
+    on_every_host(|endpoint| async move {
+        endpoint
+            .docker
+            .clone()
+            .with_timeout(PING_TIMEOUT)
+            .ping()
+            .await
+            .map_err(|err| {
+                debug!(target: "lazymc-docker-proxy::docker", "Failed to reach docker host {}: {}", endpoint.name, err);
+                count_error("")(err)
+            })
+    })
+    .await
+    .map(|_| ())
+}
+
+/// Stop containers with the label "lazymc.group=group"
+pub async fn stop(group: &str) -> Result<(), Error> {
+    on_group_host(group, |endpoint| stop_on(endpoint, group))
+        .await
+        .map(|_| ())
+}
+
+/// Stop the containers of a group on a docker host
+async fn stop_on(endpoint: &Endpoint, group: &str) -> Result<(), Error> {
+    debug!(target: "lazymc-docker-proxy::docker", "Stopping containers...");
+    let mut list_container_filters: HashMap<String, Vec<String>> =
+        HashMap::<String, Vec<String>>::new();
+
+    // find all matching running or paused containers
+    list_container_filters.insert(
+        "status".to_string(),
+        vec!["running".to_string(), "paused".to_string()],
+    );
+    list_container_filters.insert("label".to_string(), vec![format!("lazymc.group={}", group)]);
+
+    let containers: Vec<ContainerSummary> = list(endpoint, list_container_filters, group).await?;
+    debug!(target: "lazymc-docker-proxy::docker", "Found {} container(s) to stop", containers.len());
+    stop_containers(&endpoint.docker, containers, group).await
+}
+
+/// Start the containers of a group which are not running in stages ordered by their "lazymc.start_order" label,
+/// returning whether any container was found
+///
+/// Each stage waits until its containers are running, and healthy if they have a healthcheck, before the next
+/// stage is started.
+pub async fn start(group: &str) -> Result<bool, Error> {
+    match group_endpoint(group).await? {
+        Some(endpoint) => start_on(endpoint, group).await,
+        None => Ok(false),
+    }
+}
+
+/// Start the containers of a group on a docker host, returning whether any container was found
+async fn start_on(endpoint: &Endpoint, group: &str) -> Result<bool, Error> {
+    let docker: &Docker = &endpoint.docker;
+    let mut list_container_filters: HashMap<String, Vec<String>> =
+        HashMap::<String, Vec<String>>::new();
+
+    // find all matching containers, so that a missing container can be reported
+    list_container_filters.insert("label".to_string(), vec![format!("lazymc.group={}", group)]);
+
+    let containers: Vec<ContainerSummary> = list(endpoint, list_container_filters, group).await?;
+    let found: bool = !containers.is_empty();
+    let stages: Vec<Vec<ContainerSummary>> = stages(containers, container_start_order);
+    let stage_count: usize = stages.len();
+
+    for (index, stage) in stages.into_iter().enumerate() {
+        let stopped: Vec<&ContainerSummary> = stage
+            .iter()
+            .filter(|container| {
+                matches!(
+                    container.state,
+                    Some(ContainerSummaryStateEnum::EXITED)
+                        | Some(ContainerSummaryStateEnum::CREATED)
+                        | Some(ContainerSummaryStateEnum::PAUSED)
+                )
+            })
+            .collect();
+        debug!(target: "lazymc-docker-proxy::docker", "Found {} container(s) to start in stage {} of {}", stopped.len(), index + 1, stage_count);
+
+        first_error(
+            future::join_all(stopped.into_iter().map(|container| async move {
+                // a container left paused by a frozen server only needs to be resumed
+                if container.state == Some(ContainerSummaryStateEnum::PAUSED) {
+                    return unpause_container(docker, container, group).await;
+                }
+
+                info!(target: "lazymc-docker-proxy::docker", "Starting container: {}", name(container));
+                docker
+                    .start_container(
+                        container.id.as_deref().unwrap_or_default(),
+                        None::<StartContainerOptions>,
+                    )
+                    .await
+                    .map_err(|err| {
+                        error!(target: "lazymc-docker-proxy::docker", "Error starting container {}: {}", name(container), err);
+                        count_error(group)(err)
+                    })
+            }))
+            .await,
+        )?;
+
+        // the last stage is the server, whose readiness is left to lazymc
+        if index + 1 < stage_count {
+            first_error(
+                future::join_all(stage.iter().map(|container| async move {
+                    wait_until_ready(docker, &name(container), group).await
+                }))
+                .await,
+            )?;
+        }
+    }
+
+    Ok(found)
+}
+
+/// Wait until every container with the label "lazymc.group=group" is running, and healthy if it has a healthcheck
+pub async fn wait_for_group(group: &str) -> Result<(), Error> {
+    on_group_host(group, |endpoint| wait_for_group_on(endpoint, group))
+        .await
+        .map(|_| ())
+}
+
+/// Wait until every container of a group on a docker host is ready
+async fn wait_for_group_on(endpoint: &Endpoint, group: &str) -> Result<(), Error> {
+    let mut list_container_filters: HashMap<String, Vec<String>> =
+        HashMap::<String, Vec<String>>::new();
+
+    list_container_filters.insert("label".to_string(), vec![format!("lazymc.group={}", group)]);
+
+    let containers: Vec<ContainerSummary> = list(endpoint, list_container_filters, group).await?;
+    first_error(
+        future::join_all(containers.iter().map(|container| async move {
+            wait_until_ready(&endpoint.docker, &name(container), group).await
+        }))
+        .await,
+    )
+}
+
+/// Wait until a container is running, and healthy if it has a healthcheck
+async fn wait_until_ready(docker: &Docker, container: &str, group: &str) -> Result<(), Error> {
+    let not_ready = |reason: String| Error::NotReady {
+        container: container.to_string(),
+        reason,
+    };
+
+    debug!(target: "lazymc-docker-proxy::docker", "Waiting for container {} to be ready...", container);
+    let deadline: Instant = Instant::now() + STAGE_TIMEOUT;
+    loop {
+        let state = docker
+            .inspect_container(container, None::<InspectContainerOptions>)
+            .await
+            .map_err(count_error(group))?
+            .state
+            .unwrap_or_default();
+        let health: Option<HealthStatusEnum> = state.health.and_then(|health| health.status);
+
+        match (state.status, health) {
+            (
+                Some(ContainerStateStatusEnum::RUNNING),
+                None | Some(HealthStatusEnum::EMPTY) | Some(HealthStatusEnum::NONE),
+            )
+            | (Some(ContainerStateStatusEnum::RUNNING), Some(HealthStatusEnum::HEALTHY)) => {
+                info!(target: "lazymc-docker-proxy::docker", "Container {} is ready", container);
+                return Ok(());
+            }
+            (Some(ContainerStateStatusEnum::RUNNING), Some(HealthStatusEnum::UNHEALTHY)) => {
+                return Err(not_ready("is unhealthy".to_string()));
+            }
+            (Some(ContainerStateStatusEnum::EXITED), _)
+            | (Some(ContainerStateStatusEnum::DEAD), _) => {
+                return Err(not_ready(format!(
+                    "exited with code {}",
+                    state.exit_code.unwrap_or_default()
+                )));
+            }
+            _ => {}
+        }
+
+        if Instant::now() >= deadline {
+            return Err(not_ready(format!(
+                "was not ready after {} seconds",
+                STAGE_TIMEOUT.as_secs()
+            )));
+        }
+        tokio::time::sleep(READY_POLL_INTERVAL).await;
+    }
+}
+
+/// Follow the output of the running containers of a group with the label "lazymc.enabled=true" since the given time,
+/// in seconds since the unix epoch, passing each line to `line` until the containers stop
+pub async fn follow_logs(group: &str, since: u64, line: impl FnMut(&str)) -> Result<(), Error> {
+    let Some(endpoint) = group_endpoint(group).await? else {
+        return Ok(());
+    };
+
+    let mut list_container_filters: HashMap<String, Vec<String>> =
+        HashMap::<String, Vec<String>>::new();
+    list_container_filters.insert("status".to_string(), vec!["running".to_string()]);
+    list_container_filters.insert(
+        "label".to_string(),
+        vec![
+            "lazymc.enabled=true".to_string(),
+            format!("lazymc.group={}", group),
+        ],
+    );
+
+    let containers: Vec<ContainerSummary> = list(endpoint, list_container_filters, group).await?;
+    debug!(target: "lazymc-docker-proxy::docker", "Following the output of {} container(s) of group {}", containers.len(), group);
+    let options = LogsOptions {
+        follow: true,
+        stdout: true,
+        stderr: true,
+        since: since as i32,
+        ..Default::default()
+    };
+    follow_lines(
+        stream::select_all(containers.iter().map(|container| {
+            Box::pin(
+                endpoint
+                    .docker
+                    .logs(&name(container), Some(options.clone())),
+            )
+        })),
+        group,
+        line,
+    )
+    .await
+}
+
+/// Pass each line of the output of a log stream to `line` until the stream ends
+async fn follow_lines(
+    mut logs: impl Stream<Item = Result<LogOutput, bollard::errors::Error>> + Unpin,
+    group: &str,
+    mut line: impl FnMut(&str),
+) -> Result<(), Error> {
+    while let Some(output) = logs.next().await {
+        for text in output.map_err(count_error(group))?.to_string().lines() {
+            line(text.trim_end_matches('\r'));
+        }
+    }
+    Ok(())
+}
+
+/// Stop all containers with the label "lazymc.group" except those of the given groups and those with the label
+/// "lazymc.enabled=false", each group in the reverse of its start order
+pub async fn stop_all(except: &HashSet<String>) -> Result<(), Error> {
+    on_every_host(|endpoint| stop_all_on(endpoint, except))
+        .await
+        .map(|_| ())
+}
+
+/// Stop all containers belonging to a group on a docker host, except those of the given groups
+async fn stop_all_on(endpoint: &Endpoint, except: &HashSet<String>) -> Result<(), Error> {
+    let mut list_container_filters: HashMap<String, Vec<String>> =
+        HashMap::<String, Vec<String>>::new();
+
+    // find all running or paused docker containers belonging to a group, including those which only support a server
+    list_container_filters.insert(
+        "status".to_string(),
+        vec!["running".to_string(), "paused".to_string()],
+    );
+    list_container_filters.insert("label".to_string(), vec!["lazymc.group".to_string()]);
+
+    let containers: Vec<ContainerSummary> = list(endpoint, list_container_filters, "")
+        .await?
+        .into_iter()
+        .filter(|container| !disabled(container.labels.as_ref()))
+        .collect();
+    debug!(target: "lazymc-docker-proxy::docker", "Found {} container(s) to stop", containers.len());
+
+    let mut groups: HashMap<String, Vec<ContainerSummary>> = HashMap::new();
+    for container in containers {
+        let group: String = container
+            .labels
+            .as_ref()
+            .and_then(|labels| labels.get("lazymc.group"))
+            .cloned()
+            .unwrap_or_default();
+        if !except.contains(&group) {
+            groups.entry(group).or_default().push(container);
+        }
+    }
+
+    first_error(
+        future::join_all(groups.iter().map(|(group, containers)| {
+            stop_containers(&endpoint.docker, containers.clone(), group)
+        }))
+        .await,
+    )
+}
+
+/// Get the name and state of each container with the label "lazymc.group=group"
+pub async fn get_states(group: &str) -> Result<Vec<(String, String)>, Error> {
+    on_group_host(group, |endpoint| get_states_on(endpoint, group))
+        .await
+        .map(|states| states.into_iter().flatten().collect())
+}
+
+/// Get the name and state of each container of a group on a docker host
+async fn get_states_on(endpoint: &Endpoint, group: &str) -> Result<Vec<(String, String)>, Error> {
+    let mut list_container_filters: HashMap<String, Vec<String>> =
+        HashMap::<String, Vec<String>>::new();
+
+    list_container_filters.insert("label".to_string(), vec![format!("lazymc.group={}", group)]);
+
+    let containers: Vec<ContainerSummary> = list(endpoint, list_container_filters, group).await?;
+    Ok(containers
+        .into_iter()
+        .map(|container| {
+            (
+                name(&container),
+                container
+                    .state
+                    .map(|state| state.to_string())
+                    .unwrap_or_default(),
+            )
+        })
+        .collect())
+}
+
+/// Get all labels for containers with the label "lazymc.enabled=true"
+///
+/// The servers last found on a docker host which can not be listed are returned instead, so their groups keep
+/// running while the host is unreachable.
+pub async fn get_labels() -> Result<Vec<ContainerLabels>, Error> {
+    static LAST_FOUND: Mutex<BTreeMap<String, Vec<ContainerLabels>>> = Mutex::new(BTreeMap::new());
+
+    on_every_host(|endpoint| async move {
+        let result: Result<Vec<ContainerLabels>, Error> = get_labels_on(endpoint).await;
+        let mut last_found = LAST_FOUND.lock().unwrap_or_else(|err| err.into_inner());
+        match result {
+            Ok(label_sets) => {
+                last_found.insert(endpoint.name.clone(), label_sets.clone());
+                Ok(label_sets)
+            }
+            Err(err) => match last_found.get(&endpoint.name) {
+                Some(label_sets) => {
+                    warn!(target: "lazymc-docker-proxy::docker", "Failed to list the containers of docker host {}, using the servers last found on it: {}", endpoint.name, err);
+                    Ok(label_sets.clone())
+                }
+                None => Err(err),
+            },
+        }
+    })
+    .await
+    .map(|label_sets| label_sets.into_iter().flatten().collect())
+}
+
+/// Get all labels for containers with the label "lazymc.enabled=true" on a docker host
+///
+/// The server address of a container on a remote docker host is the machine of the host and the port the server
+/// is published on, which is known once the container has been started unless the port binding is fixed.
+async fn get_labels_on(endpoint: &Endpoint) -> Result<Vec<ContainerLabels>, Error> {
+    let mut list_container_filters: HashMap<String, Vec<String>> =
+        HashMap::<String, Vec<String>>::new();
+
+    // find all docker containers with the label "lazymc.enabled=true"
+    list_container_filters.insert("label".to_string(), vec![format!("lazymc.enabled=true")]);
+
+    // find all matching containers and then get their labels
+    let docker: &Docker = &endpoint.docker;
+    let containers: Vec<ContainerSummary> = list(endpoint, list_container_filters, "").await?;
+    debug!(target: "lazymc-docker-proxy::docker", "Found {} container(s) to get labels on docker host {}", containers.len(), endpoint.name);
+
+    let mut label_sets: Vec<ContainerLabels> = Vec::new();
+    for container in containers {
+        let mut labels: HashMap<String, String> = container.labels.clone().unwrap_or_default();
+        let ip_address: Option<String> = match &endpoint.remote {
+            // the IP address of a container on a remote docker host can not be reached, its published port can
+            Some(machine) => {
+                let port: Option<u16> = labels
+                    .get("lazymc.server.address")
+                    .and_then(|address| address.rsplit(':').next())
+                    .and_then(|port| port.parse().ok());
+                match port {
+                    Some(port) => published_port(docker, &container, port)
+                        .await
+                        .map(|published| {
+                            labels.insert(
+                                "lazymc.server.address".to_string(),
+                                format!("{}:{}", machine, published),
+                            );
+                            machine.clone()
+                        }),
+                    None => None,
+                }
+            }
+            None => resolve_ip_address(docker, &container).await,
+        };
+        label_sets.push(server_labels(name(&container), labels, ip_address));
+    }
+    Ok(label_sets)
+}
+
+/// Watch for containers with the label "lazymc.enabled=true" being created, destroyed, renamed,
+/// started or connected to and disconnected from a network
+///
+/// Returns when the event stream of a docker host ends or the receiver is dropped.
+pub async fn watch(sender: Sender<ContainerEvent>) -> Result<(), Error> {
+    let watches = endpoints()?
+        .iter()
+        .map(|endpoint| Box::pin(watch_on(endpoint, sender.clone())));
+    future::select_all(watches).await.0
+}
+
+/// Watch for containers with the label "lazymc.enabled=true" changing on a docker host
+async fn watch_on(endpoint: &Endpoint, sender: Sender<ContainerEvent>) -> Result<(), Error> {
+    let docker: &Docker = &endpoint.docker;
+
+    let mut event_filters: HashMap<String, Vec<String>> = HashMap::<String, Vec<String>>::new();
+
+    // the label filter would also apply to network events, so managed containers are matched below instead
+    event_filters.insert(
+        "type".to_string(),
+        vec!["container".to_string(), "network".to_string()],
+    );
+    event_filters.insert(
+        "event".to_string(),
+        vec![
+            "create".to_string(),
+            "destroy".to_string(),
+            "rename".to_string(),
+            "start".to_string(),
+            "connect".to_string(),
+            "disconnect".to_string(),
+        ],
+    );
+
+    let mut events = docker.events(Some(EventsOptions {
+        filters: Some(event_filters),
+        ..Default::default()
+    }));
+
+    debug!(target: "lazymc-docker-proxy::docker", "Watching for container events on docker host {}...", endpoint.name);
+    while let Some(event) = events.next().await {
+        let event = event.map_err(count_error(""))?;
+
+        let attributes: HashMap<String, String> = event
+            .actor
+            .and_then(|actor| actor.attributes)
+            .unwrap_or_default();
+
+        // container events carry the container labels, network events only the container id
+        let labels: HashMap<String, String> = match event.typ {
+            Some(EventMessageTypeEnum::NETWORK) => match attributes.get("container") {
+                Some(id) => docker
+                    .inspect_container(id, None::<InspectContainerOptions>)
+                    .await
+                    .ok()
+                    .and_then(|container| container.config)
+                    .and_then(|config| config.labels)
+                    .unwrap_or_default(),
+                None => continue,
+            },
+            _ => attributes,
+        };
+
+        if labels.get("lazymc.enabled").map(String::as_str) != Some("true")
+            || !has_labels(&labels, scope().await?)
+        {
+            continue;
+        }
+
+        let container_event = ContainerEvent {
+            action: event.action.unwrap_or_default(),
+            group: labels.get("lazymc.group").cloned(),
+        };
+        if sender.send(container_event).is_err() {
+            return Ok(());
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,390 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::Sender;
+use std::time::Instant;
+
+use bollard::models::{Service, Task, TaskState};
+use bollard::query_parameters::{
+    EventsOptions, InspectServiceOptions, ListServicesOptions, ListTasksOptions, LogsOptions,
+    UpdateServiceOptions,
+};
+use bollard::Docker;
+use futures::{future, stream, StreamExt};
+
+use super::{
+    client, count_error, follow_lines, has_labels, scope, READY_POLL_INTERVAL, STAGE_TIMEOUT,
+};
+use crate::backend::{
+    disabled, first_error, server_labels, stages, start_order, ContainerEvent, ContainerLabels,
+    Error,
+};
+
+/// Get the name of a service
+fn name(service: &Service) -> String {
+    service
+        .spec
+        .as_ref()
+        .and_then(|spec| spec.name.clone())
+        .unwrap_or_default()
+}
+
+/// Get the labels of a service
+fn labels(service: &Service) -> Option<&HashMap<String, String>> {
+    service.spec.as_ref().and_then(|spec| spec.labels.as_ref())
+}
+
+/// Get the start order of a service
+fn service_start_order(service: &Service) -> i32 {
+    start_order(labels(service), &format!("service {}", name(service)))
+}
+
+/// List all services with the given label in the scope of this proxy instance
+async fn list(label: String, group: &str) -> Result<Vec<Service>, Error> {
+    let mut labels: Vec<String> = vec![label];
+    labels.extend(scope().await?.iter().cloned());
+    client()?
+        .list_services(Some(ListServicesOptions {
+            filters: Some(HashMap::from([("label".to_string(), labels)])),
+            ..Default::default()
+        }))
+        .await
+        .map_err(count_error(group))
+}
+
+/// List the tasks of a service, including those which are shutting down or have failed
+async fn tasks(service: &str, group: &str) -> Result<Vec<Task>, Error> {
+    client()?
+        .list_tasks(Some(ListTasksOptions {
+            filters: Some(HashMap::from([(
+                "service".to_string(),
+                vec![service.to_string()],
+            )])),
+        }))
+        .await
+        .map_err(count_error(group))
+}
+
+/// Get the state of a task
+fn state(task: &Task) -> Option<TaskState> {
+    task.status.as_ref().and_then(|status| status.state)
+}
+
+/// Scale a replicated service to the given number of replicas
+async fn scale(service: &Service, replicas: i64, group: &str) -> Result<(), Error> {
+    let docker: &Docker = client()?;
+    let name: String = name(service);
+    let mut spec = service.spec.clone().unwrap_or_default();
+    let Some(replicated) = spec.mode.as_mut().and_then(|mode| mode.replicated.as_mut()) else {
+        warn!(target: "lazymc-docker-proxy::docker", "Service {} is not replicated and can not be scaled", name);
+        return Ok(());
+    };
+    if replicated.replicas == Some(replicas) {
+        return Ok(());
+    }
+    replicated.replicas = Some(replicas);
+
+    info!(target: "lazymc-docker-proxy::docker", "Scaling service {} to {} replica(s)", name, replicas);
+    let version: i32 = service
+        .version
+        .as_ref()
+        .and_then(|version| version.index)
+        .unwrap_or_default() as i32;
+    docker
+        .update_service(
+            &name,
+            spec,
+            UpdateServiceOptions {
+                version,
+                ..Default::default()
+            },
+            None,
+        )
+        .await
+        .map(|_| ())
+        .map_err(|err| {
+            error!(target: "lazymc-docker-proxy::docker", "Error scaling service {}: {}", name, err);
+            count_error(group)(err)
+        })
+}
+
+/// Wait until a task of a service is running
+async fn wait_until_running(service: &str, group: &str) -> Result<(), Error> {
+    debug!(target: "lazymc-docker-proxy::docker", "Waiting for a task of service {} to be running...", service);
+    let deadline: Instant = Instant::now() + STAGE_TIMEOUT;
+    loop {
+        let tasks: Vec<Task> = tasks(service, group).await?;
+        if tasks
+            .iter()
+            .any(|task| state(task) == Some(TaskState::RUNNING))
+        {
+            info!(target: "lazymc-docker-proxy::docker", "Service {} is running", service);
+            return Ok(());
+        }
+
+        if Instant::now() >= deadline {
+            // swarm replaces failed tasks, so the last error is only reported once the wait is over
+            let message: Option<String> = tasks
+                .iter()
+                .filter_map(|task| task.status.as_ref())
+                .find_map(|status| status.err.clone().or_else(|| status.message.clone()));
+            return Err(Error::NotReady {
+                container: service.to_string(),
+                reason: format!(
+                    "was not running after {} seconds{}",
+                    STAGE_TIMEOUT.as_secs(),
+                    message.map_or(String::new(), |message| format!(": {}", message))
+                ),
+            });
+        }
+        tokio::time::sleep(READY_POLL_INTERVAL).await;
+    }
+}
+
+/// Wait until a service has no running tasks
+async fn wait_until_stopped(service: &str, group: &str) -> Result<(), Error> {
+    let deadline: Instant = Instant::now() + STAGE_TIMEOUT;
+    loop {
+        let running: bool = tasks(service, group).await?.iter().any(|task| {
+            matches!(
+                state(task),
+                Some(TaskState::RUNNING) | Some(TaskState::STARTING)
+            )
+        });
+        if !running {
+            info!(target: "lazymc-docker-proxy::docker", "Service {} stopped", service);
+            return Ok(());
+        }
+
+        if Instant::now() >= deadline {
+            return Err(Error::NotReady {
+                container: service.to_string(),
+                reason: format!(
+                    "was still running after {} seconds",
+                    STAGE_TIMEOUT.as_secs()
+                ),
+            });
+        }
+        tokio::time::sleep(READY_POLL_INTERVAL).await;
+    }
+}
+
+/// Scale the given services to 0 replicas in the reverse of their start order, the services of each stage concurrently
+async fn stop_services(services: Vec<Service>, group: &str) -> Result<(), Error> {
+    let mut result: Result<(), Error> = Ok(());
+    for stage in stages(services, service_start_order).into_iter().rev() {
+        let results: Vec<Result<(), Error>> =
+            future::join_all(stage.iter().map(|service| async move {
+                scale(service, 0, group).await?;
+                wait_until_stopped(&name(service), group).await
+            }))
+            .await;
+
+        // keep stopping the remaining stages, a service which failed to stop should not keep the others running
+        result = result.and(first_error(results));
+    }
+    result
+}
+
+/// Scale the services with the label "lazymc.group=group" to 0 replicas
+pub async fn stop(group: &str) -> Result<(), Error> {
+    let services: Vec<Service> = list(format!("lazymc.group={}", group), group).await?;
+    debug!(target: "lazymc-docker-proxy::docker", "Found {} service(s) to stop", services.len());
+    stop_services(services, group).await
+}
+
+/// Scale the services of a group to 1 replica in stages ordered by their "lazymc.start_order" label, waiting for the
+/// task of each service to be running, returning whether any service was found
+pub async fn start(group: &str) -> Result<bool, Error> {
+    let services: Vec<Service> = list(format!("lazymc.group={}", group), group).await?;
+    let found: bool = !services.is_empty();
+    let stages: Vec<Vec<Service>> = stages(services, service_start_order);
+    let stage_count: usize = stages.len();
+
+    for (index, stage) in stages.into_iter().enumerate() {
+        debug!(target: "lazymc-docker-proxy::docker", "Found {} service(s) to start in stage {} of {}", stage.len(), index + 1, stage_count);
+        first_error(
+            future::join_all(stage.iter().map(|service| async move {
+                scale(service, 1, group).await?;
+                wait_until_running(&name(service), group).await
+            }))
+            .await,
+        )?;
+    }
+
+    Ok(found)
+}
+
+/// Wait until every service with the label "lazymc.group=group" has a running task
+pub async fn wait_for_group(group: &str) -> Result<(), Error> {
+    let services: Vec<Service> = list(format!("lazymc.group={}", group), group).await?;
+    first_error(
+        future::join_all(
+            services
+                .iter()
+                .map(|service| async move { wait_until_running(&name(service), group).await }),
+        )
+        .await,
+    )
+}
+
+/// Follow the output of the services of a group with the label "lazymc.enabled=true" since the given time, in
+/// seconds since the unix epoch, passing each line to `line` until the services stop
+pub async fn follow_logs(group: &str, since: u64, line: impl FnMut(&str)) -> Result<(), Error> {
+    let docker: &Docker = client()?;
+    let services: Vec<Service> = list(format!("lazymc.group={}", group), group)
+        .await?
+        .into_iter()
+        .filter(|service| {
+            labels(service)
+                .is_some_and(|labels| has_labels(labels, &["lazymc.enabled=true".to_string()]))
+        })
+        .collect();
+    debug!(target: "lazymc-docker-proxy::docker", "Following the output of {} service(s) of group {}", services.len(), group);
+    let options = LogsOptions {
+        follow: true,
+        stdout: true,
+        stderr: true,
+        since: since as i32,
+        ..Default::default()
+    };
+    follow_lines(
+        stream::select_all(
+            services.iter().map(|service| {
+                Box::pin(docker.service_logs(&name(service), Some(options.clone())))
+            }),
+        ),
+        group,
+        line,
+    )
+    .await
+}
+
+/// Scale all services with the label "lazymc.group" to 0 replicas except those of the given groups and those with the
+/// label "lazymc.enabled=false", each group in the reverse of its start order
+pub async fn stop_all(except: &HashSet<String>) -> Result<(), Error> {
+    let services: Vec<Service> = list("lazymc.group".to_string(), "")
+        .await?
+        .into_iter()
+        .filter(|service| !disabled(labels(service)))
+        .collect();
+    debug!(target: "lazymc-docker-proxy::docker", "Found {} service(s) to stop", services.len());
+
+    let mut groups: HashMap<String, Vec<Service>> = HashMap::new();
+    for service in services {
+        let group: String = labels(&service)
+            .and_then(|labels| labels.get("lazymc.group"))
+            .cloned()
+            .unwrap_or_default();
+        if !except.contains(&group) {
+            groups.entry(group).or_default().push(service);
+        }
+    }
+
+    first_error(
+        future::join_all(
+            groups
+                .into_iter()
+                .map(|(group, services)| async move { stop_services(services, &group).await }),
+        )
+        .await,
+    )
+}
+
+/// Get the name of each service with the label "lazymc.group=group" and the state of its task
+pub async fn get_states(group: &str) -> Result<Vec<(String, String)>, Error> {
+    let services: Vec<Service> = list(format!("lazymc.group={}", group), group).await?;
+    let mut states: Vec<(String, String)> = Vec::new();
+    for service in services {
+        let name: String = name(&service);
+        // tasks which swarm is no longer keeping running belong to earlier wakes
+        let state: String = tasks(&name, group)
+            .await?
+            .iter()
+            .filter(|task| task.desired_state == Some(TaskState::RUNNING))
+            .find_map(state)
+            .map_or("stopped".to_string(), |state| state.to_string());
+        states.push((name, state));
+    }
+    Ok(states)
+}
+
+/// Get all labels for services with the label "lazymc.enabled=true"
+pub async fn get_labels() -> Result<Vec<ContainerLabels>, Error> {
+    let services: Vec<Service> = list("lazymc.enabled=true".to_string(), "").await?;
+    debug!(target: "lazymc-docker-proxy::docker", "Found {} service(s) to get labels", services.len());
+
+    // the service name resolves to the virtual IP of the service, which unlike the IP of its task does not change
+    // when the service is scaled
+    Ok(services
+        .into_iter()
+        .map(|service| {
+            server_labels(
+                name(&service),
+                labels(&service).cloned().unwrap_or_default(),
+                None,
+            )
+        })
+        .collect())
+}
+
+/// Watch for services with the label "lazymc.enabled=true" being created, updated or removed
+///
+/// Returns when the event stream ends or the receiver is dropped.
+pub async fn watch(sender: Sender<ContainerEvent>) -> Result<(), Error> {
+    let docker: &Docker = client()?;
+
+    let mut events = docker.events(Some(EventsOptions {
+        filters: Some(HashMap::from([
+            ("type".to_string(), vec!["service".to_string()]),
+            (
+                "event".to_string(),
+                vec![
+                    "create".to_string(),
+                    "update".to_string(),
+                    "remove".to_string(),
+                ],
+            ),
+        ])),
+        ..Default::default()
+    }));
+
+    debug!(target: "lazymc-docker-proxy::docker", "Watching for service events...");
+    while let Some(event) = events.next().await {
+        let event = event.map_err(count_error(""))?;
+        let action: String = event.action.unwrap_or_default();
+        let attributes: HashMap<String, String> = event
+            .actor
+            .and_then(|actor| actor.attributes)
+            .unwrap_or_default();
+
+        // service events only carry the service name
+        let Some(service) = attributes.get("name") else {
+            continue;
+        };
+
+        let group: Option<String> = match action.as_str() {
+            // a removed service can not be inspected, so its group is found by reconciling
+            "remove" => None,
+            _ => {
+                let labels: HashMap<String, String> = docker
+                    .inspect_service(service, None::<InspectServiceOptions>)
+                    .await
+                    .ok()
+                    .and_then(|service| service.spec)
+                    .and_then(|spec| spec.labels)
+                    .unwrap_or_default();
+                if labels.get("lazymc.enabled").map(String::as_str) != Some("true")
+                    || !has_labels(&labels, scope().await?)
+                {
+                    continue;
+                }
+                labels.get("lazymc.group").cloned()
+            }
+        };
+
+        if sender.send(ContainerEvent { action, group }).is_err() {
+            return Ok(());
+        }
+    }
+
+    Ok(())
+}
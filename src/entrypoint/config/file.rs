@@ -0,0 +1,196 @@
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::env::var;
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// The proxy configuration file, shared by every group
+///
+/// Keys are label names without the `lazymc.` prefix, either dotted or as nested tables:
+///
+/// ```toml
+/// [defaults]
+/// time.sleep_after = 300
+///
+/// [groups.mc]
+/// server.address = "mc:25565"
+/// motd.sleeping = """
+/// Multi-line
+/// message"""
+///
+/// [hosts.big]
+/// url = "tcp://192.168.1.20:2376"
+/// ```
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    /// Values used for every group unless a container label or the group sets them
+    #[serde(default)]
+    defaults: toml::Table,
+    /// Values for a single group, keyed by group name, which take precedence over container labels
+    #[serde(default)]
+    groups: BTreeMap<String, toml::Table>,
+    /// Docker hosts servers run on besides the default one, keyed by name
+    #[serde(default)]
+    hosts: BTreeMap<String, Host>,
+}
+
+/// A docker host servers run on
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Host {
+    /// The address of the docker API, such as `tcp://192.168.1.20:2376` or `unix:///run/other.sock`
+    pub url: String,
+    /// Connect with the TLS client certificate in `cert_path`, always done for `https://` addresses
+    #[serde(default)]
+    pub tls_verify: bool,
+    /// The directory with the `ca.pem`, `cert.pem` and `key.pem` of the TLS client certificate
+    pub cert_path: Option<String>,
+    /// The address of the machine, which servers are reached through by their published ports, defaults to the
+    /// host of `url`
+    pub address: Option<String>,
+}
+
+/// Container labels merged with the configuration file
+pub struct Merged {
+    pub labels: HashMap<String, String>,
+    /// Where each value taken from the file came from, keyed by label name
+    pub origins: HashMap<String, String>,
+}
+
+/// Get the path of the configuration file, if one is used
+pub fn path() -> Option<String> {
+    var("LAZYMC_CONFIG_FILE")
+        .ok()
+        .filter(|path| !path.is_empty())
+}
+
+/// The configuration file as last read, kept until the file at its path is modified
+struct Cached {
+    path: String,
+    /// The modification time and size of the file when it was read, unknown if it did not exist
+    modified: Option<(SystemTime, u64)>,
+    file: Result<Arc<ConfigFile>, String>,
+}
+
+/// The configuration file as last read
+static CACHE: Mutex<Option<Cached>> = Mutex::new(None);
+
+/// Read the configuration file, it is only read again once it is modified so changes are picked up when containers
+/// are discovered, and a file which can not be read is reported once
+fn read(path: &str) -> Result<Arc<ConfigFile>, String> {
+    let modified: Option<(SystemTime, u64)> = fs::metadata(path)
+        .and_then(|metadata| Ok((metadata.modified()?, metadata.len())))
+        .ok();
+
+    let mut cache = CACHE.lock().unwrap_or_else(|err| err.into_inner());
+    if let Some(cached) = cache
+        .as_ref()
+        .filter(|cached| cached.path == path && cached.modified == modified)
+    {
+        return cached.file.clone();
+    }
+
+    let file: Result<Arc<ConfigFile>, String> = fs::read_to_string(path)
+        .map_err(|err| err.to_string())
+        .and_then(|content| toml::from_str(&content).map_err(|err| err.message().to_string()))
+        .map(Arc::new);
+    match &file {
+        Ok(_) => {
+            debug!(target: "lazymc-docker-proxy::entrypoint::config", "Read the configuration file {}", path)
+        }
+        Err(err) => {
+            error!(target: "lazymc-docker-proxy::entrypoint::config", "Failed to read the configuration file {}: {}", path, err)
+        }
+    }
+    *cache = Some(Cached {
+        path: path.to_string(),
+        modified,
+        file: file.clone(),
+    });
+    file
+}
+
+/// Get the docker hosts of the configuration file, keyed by name
+pub fn hosts() -> Result<BTreeMap<String, Host>, String> {
+    let Some(path) = path() else {
+        return Ok(BTreeMap::new());
+    };
+    read(&path)
+        .map(|file| file.hosts.clone())
+        .map_err(|err| format!("failed to read {}: {}", path, err))
+}
+
+/// Flatten a table into `lazymc.*` label names and values
+fn flatten(prefix: &str, table: &toml::Table, values: &mut BTreeMap<String, String>) {
+    for (key, value) in table.iter() {
+        let name: String = format!("{}.{}", prefix, key);
+        match value {
+            toml::Value::Table(table) => flatten(&name, table, values),
+            toml::Value::String(value) => {
+                values.insert(name, value.clone());
+            }
+            // lists are written as comma separated values, like the labels they replace
+            toml::Value::Array(items) => {
+                let items: Vec<String> = items
+                    .iter()
+                    .map(|item| match item {
+                        toml::Value::String(item) => item.clone(),
+                        item => item.to_string(),
+                    })
+                    .collect();
+                values.insert(name, items.join(","));
+            }
+            value => {
+                values.insert(name, value.to_string());
+            }
+        }
+    }
+}
+
+/// Merge the configuration file with the labels of a container
+///
+/// Values are taken from, in increasing order of precedence, the `[defaults]` table, the container labels
+/// and the `[groups.<name>]` table of the container's group.
+pub fn merge(labels: HashMap<String, String>) -> Result<Merged, String> {
+    let Some(path) = path() else {
+        return Ok(Merged {
+            labels,
+            origins: HashMap::new(),
+        });
+    };
+    let file: Arc<ConfigFile> =
+        read(&path).map_err(|err| format!("failed to read {}: {}", path, err))?;
+
+    let mut values: HashMap<String, String> = HashMap::new();
+    let mut origins: HashMap<String, String> = HashMap::new();
+
+    let mut defaults: BTreeMap<String, String> = BTreeMap::new();
+    flatten("lazymc", &file.defaults, &mut defaults);
+    for (name, value) in defaults {
+        origins.insert(name.clone(), format!("{} [defaults]", path));
+        values.insert(name, value);
+    }
+
+    for (name, value) in labels {
+        origins.remove(&name);
+        values.insert(name, value);
+    }
+
+    let group: Option<String> = values.get("lazymc.group").cloned();
+    if let Some((group, group_table)) = group.and_then(|group| file.groups.get_key_value(&group)) {
+        let mut overrides: BTreeMap<String, String> = BTreeMap::new();
+        flatten("lazymc", group_table, &mut overrides);
+        let table: String = format!("{} [groups.{}]", path, group);
+        for (name, value) in overrides {
+            origins.insert(name.clone(), table.clone());
+            values.insert(name, value);
+        }
+    }
+
+    Ok(Merged {
+        labels: values,
+        origins,
+    })
+}
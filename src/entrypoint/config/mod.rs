@@ -0,0 +1,629 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::env::var;
+use std::fmt::{self, Write as _};
+use std::fs::{self, File};
+use std::io::Write;
+use std::net::ToSocketAddrs;
+use std::path::Path;
+use std::process::{exit, Command};
+use version_compare::Version;
+
+use crate::{backend, health, notify::Notifiers};
+
+pub mod file;
+mod schema;
+use file::Merged;
+use schema::{per_container, Labels, Problem, LABELS};
+
+/// Keys of the lazymc config which are always set by lazymc-docker-proxy
+const FIXED_KEYS: &[&str] = &[
+    "server.command",
+    "server.wake_on_start",
+    "server.wake_on_crash",
+    "advanced.rewrite_server_properties",
+];
+
+/// The labels of a container failed validation
+pub struct InvalidLabels {
+    /// The name of the container
+    pub container: String,
+    /// The value of the "lazymc.group" label, if it was set
+    pub group: Option<String>,
+    pub problems: Vec<Problem>,
+}
+
+impl fmt::Display for InvalidLabels {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid labels on container {}:", self.container)?;
+        for problem in self.problems.iter() {
+            write!(f, "\n  - {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+/// lazymc dropped support minecraft servers with version less than 1.20.3
+fn is_legacy(version: Option<String>) -> bool {
+    if version.is_none() {
+        return false;
+    }
+
+    Version::from(version.unwrap().as_ref()) < Version::from("1.20.3")
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct ServerSection {
+    address: Option<String>,
+    block_banned_ips: Option<bool>,
+    command: Option<String>,
+    directory: Option<String>,
+    drop_banned_ips: Option<bool>,
+    forge: Option<bool>,
+    freeze_process: Option<bool>,
+    probe_on_start: Option<bool>,
+    send_proxy_v2: Option<bool>,
+    start_timeout: Option<i32>,
+    stop_timeout: Option<i32>,
+    wake_on_crash: Option<bool>,
+    wake_on_start: Option<bool>,
+    wake_whitelist: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct PublicSection {
+    address: Option<String>,
+    version: Option<String>,
+    protocol: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct TimeSection {
+    minimum_online_time: Option<i32>,
+    sleep_after: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct JoinSection {
+    methods: Option<Vec<String>>,
+    kick: JoinKickSection,
+    hold: JoinHoldSection,
+    forward: JoinForwardSection,
+    lobby: JoinLobbySection,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct JoinKickSection {
+    starting: Option<String>,
+    stopping: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct JoinHoldSection {
+    timeout: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct JoinForwardSection {
+    address: Option<String>,
+    send_proxy_v2: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct JoinLobbySection {
+    timeout: Option<i32>,
+    message: Option<String>,
+    ready_sound: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct MotdSection {
+    sleeping: Option<String>,
+    starting: Option<String>,
+    stopping: Option<String>,
+    from_server: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct LockoutSection {
+    enabled: Option<bool>,
+    message: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct AdvancedSection {
+    rewrite_server_properties: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct ConfigSection {
+    version: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Config {
+    advanced: AdvancedSection,
+    config: ConfigSection,
+    join: JoinSection,
+    lockout: LockoutSection,
+    motd: MotdSection,
+    public: PublicSection,
+    server: ServerSection,
+    time: TimeSection,
+    #[serde(skip)]
+    start_command: String,
+    #[serde(skip)]
+    config_file: String,
+    #[serde(skip)]
+    group: String,
+    #[serde(skip)]
+    resolved_ip: bool,
+    /// Where the value of each key came from, keyed by its dotted path
+    #[serde(skip)]
+    sources: BTreeMap<String, String>,
+    #[serde(skip)]
+    notifiers: Notifiers,
+}
+
+/// Configuration for the lazymc server
+impl Config {
+    /// Generate the start command for the lazymc server
+    pub fn start_command(&self) -> Command {
+        // Start the server if the IP address has not been resolved
+        if !self.resolved_ip {
+            let result: Result<bool, backend::Error> =
+                backend::runtime().block_on(backend::start(self.group()));
+            match &result {
+                Ok(true) => {}
+                Ok(false) => {
+                    error!(target: "lazymc-docker-proxy::entrypoint::config", "No server found with the label lazymc.group={}", self.group())
+                }
+                Err(err) => {
+                    error!(target: "lazymc-docker-proxy::entrypoint::config", "Failed to start containers for group {}: {}", self.group(), err)
+                }
+            }
+            health::record_start(
+                self.group(),
+                *result.as_ref().unwrap_or(&true),
+                result.is_ok(),
+            );
+        }
+
+        let mut command: Command = Command::new(self.start_command.clone());
+
+        // lazymc lifecycle messages are logged at info level and are needed for metrics, they
+        // are still filtered by the configured level when they are wrapped
+        if log::max_level() < log::LevelFilter::Info {
+            command.env("RUST_LOG", "info");
+        }
+
+        // child output is parsed in the default format before it is logged as JSON
+        command.env_remove("LAZYMC_LOG_FORMAT");
+
+        command.arg("start");
+        command.arg("--config");
+        command.arg(self.config_file.clone());
+        command
+    }
+
+    /// Get the group name for the lazymc server
+    pub fn group(&self) -> &str {
+        &self.group
+    }
+
+    /// Get the port on the proxy the server is accessible from
+    pub fn port(&self) -> Option<u16> {
+        self.public
+            .address
+            .as_ref()
+            .and_then(|address| address.rsplit(':').next())
+            .and_then(|port| port.parse().ok())
+    }
+
+    /// Get the address lazymc uses to reach the minecraft server
+    pub fn server_address(&self) -> Option<&str> {
+        self.server.address.as_deref()
+    }
+
+    /// Whether the server address was resolved to an IP address
+    pub fn resolved_ip(&self) -> bool {
+        self.resolved_ip
+    }
+
+    /// Get where notifications about the group are sent
+    pub fn notifiers(&self) -> &Notifiers {
+        &self.notifiers
+    }
+
+    /// Have lazymc consider the server online as soon as it starts, used when the server is already running
+    pub fn wake_on_start(mut self) -> Self {
+        self.server.wake_on_start = Some(true);
+        self
+    }
+
+    /// Convert the configuration to a TOML string
+    fn as_toml_string(&self) -> String {
+        toml::to_string(self).unwrap()
+    }
+
+    /// Convert the configuration to a TOML string, with a comment above each key describing where its value came from
+    pub fn as_annotated_toml_string(&self) -> String {
+        let table: toml::Table = toml::Table::try_from(self).unwrap();
+        let mut output: String = String::new();
+        self.write_annotated_table(&mut output, "", &table);
+        output.trim_start().to_string()
+    }
+
+    /// Write a table of the configuration and its sub-tables, annotating each key
+    fn write_annotated_table(&self, output: &mut String, path: &str, table: &toml::Table) {
+        let (tables, values): (Vec<_>, Vec<_>) =
+            table.iter().partition(|(_, value)| value.is_table());
+
+        if !values.is_empty() {
+            writeln!(output, "\n[{}]", path).unwrap();
+        }
+        for (key, value) in values {
+            if let Some(source) = self.sources.get(&format!("{}.{}", path, key)) {
+                writeln!(output, "# {}", source).unwrap();
+            }
+            writeln!(output, "{} = {}", key, value).unwrap();
+        }
+
+        for (key, value) in tables {
+            let path: String = match path.is_empty() {
+                true => key.clone(),
+                false => format!("{}.{}", path, key),
+            };
+            self.write_annotated_table(output, &path, value.as_table().unwrap());
+        }
+    }
+
+    /// Get the name of the lazymc configuration file
+    pub fn config_file(&self) -> &str {
+        &self.config_file
+    }
+
+    /// Create the lazymc configuration file
+    pub fn create_file(&self) {
+        let toml = self.as_toml_string();
+        let file_name: &String = &format!("lazymc.{}.toml", self.group.clone());
+        let path: &Path = Path::new(file_name);
+        let mut file = File::create(path).unwrap();
+        file.write_all(toml.as_ref()).unwrap();
+        debug!(target: "lazymc-docker-proxy::entrypoint::config", "`generated`: {}\n\n{}", path.display(), toml);
+    }
+
+    /// Remove the lazymc configuration file
+    pub fn remove_file(&self) {
+        let path: &Path = Path::new(&self.config_file);
+        if let Err(err) = fs::remove_file(path) {
+            warn!(target: "lazymc-docker-proxy::entrypoint::config", "Failed to remove {}: {}", path.display(), err);
+        } else {
+            debug!(target: "lazymc-docker-proxy::entrypoint::config", "`removed`: {}", path.display());
+        }
+    }
+
+    /// Create a new configuration from container labels
+    pub fn from_container_labels(
+        container: &str,
+        labels: HashMap<String, String>,
+    ) -> Result<Self, InvalidLabels> {
+        let Merged { labels, origins } =
+            file::merge(labels.clone()).map_err(|message| InvalidLabels {
+                container: container.to_string(),
+                group: labels.get("lazymc.group").cloned(),
+                problems: vec![Problem {
+                    label: "LAZYMC_CONFIG_FILE".to_string(),
+                    message,
+                }],
+            })?;
+        let legacy: bool = is_legacy(labels.get("lazymc.public.version").cloned());
+        let version_var: &str = match legacy {
+            true => "LAZYMC_LEGACY_VERSION",
+            false => "LAZYMC_VERSION",
+        };
+        let version: Option<String> = var(version_var).ok();
+
+        let invalid = |problems: Vec<Problem>| InvalidLabels {
+            container: container.to_string(),
+            group: labels.get("lazymc.group").cloned(),
+            // name the file for values which did not come from a label
+            problems: problems
+                .into_iter()
+                .map(|problem| match origins.get(&problem.label) {
+                    Some(origin) => Problem {
+                        label: format!("{} in {}", problem.label, origin),
+                        message: problem.message,
+                    },
+                    None => problem,
+                })
+                .collect(),
+        };
+
+        // these are read from the container when it is started or stopped, the file is not consulted then
+        let mut misplaced: Vec<Problem> = origins
+            .keys()
+            .filter(|name| per_container(name))
+            .map(|name| Problem {
+                label: name.clone(),
+                message: "can only be set as a container label".to_string(),
+            })
+            .collect();
+        if !misplaced.is_empty() {
+            misplaced.sort_by(|a, b| a.label.cmp(&b.label));
+            return Err(invalid(misplaced));
+        }
+
+        let labels: Labels = Labels::validate(container, version.as_deref(), labels.clone())
+            .map_err(invalid)?
+            .with_origins(origins.clone());
+        let version: String = version.ok_or_else(|| {
+            invalid(vec![Problem {
+                label: version_var.to_string(),
+                message: "environment variable is not set".to_string(),
+            }])
+        })?;
+        let group: String = labels.string("lazymc.group").unwrap();
+
+        // Check if the IP address has been resolved
+        let mut resolved_ip = true;
+
+        let mut command: String = format!("lazymc-docker-proxy --command --group {}", group);
+        if labels.bool("lazymc.start.wait_for_health") == Some(true) {
+            command.push_str(" --wait-for-health");
+        }
+        if labels.bool("lazymc.server.freeze_process") == Some(true) {
+            command.push_str(" --freeze");
+        }
+
+        let server_section: ServerSection = ServerSection {
+            address: labels.string("lazymc.server.address")
+                .and_then(|address| address.to_socket_addrs().ok())
+                .and_then(|mut addrs| addrs.find(|addr| addr.is_ipv4()))
+                .and_then(|addr| addr.to_string().parse().ok())
+                .or_else(|| {
+                    warn!(target: "lazymc-docker-proxy::entrypoint::config", "Failed to resolve IP address from lazymc.server.address. Falling back to the value provided.");
+                    resolved_ip = false;
+                    labels.string("lazymc.server.address")
+                }),
+            directory: labels.string("lazymc.server.directory"),
+            command: Some(command),
+            freeze_process: labels.bool("lazymc.server.freeze_process"),
+            // If the IP address was not resolved, wake_on_start should be true
+            wake_on_start: Some(!resolved_ip),
+            wake_on_crash: Some(true),
+            wake_whitelist: labels.bool("lazymc.server.wake_whitelist"),
+            block_banned_ips: labels.bool("lazymc.server.block_banned_ips"),
+            drop_banned_ips: labels.bool("lazymc.server.drop_banned_ips"),
+            probe_on_start: labels.bool("lazymc.server.probe_on_start"),
+            forge: labels.bool("lazymc.server.forge"),
+            start_timeout: labels.int("lazymc.server.start_timeout"),
+            stop_timeout: labels.int("lazymc.server.stop_timeout"),
+            send_proxy_v2: labels.bool("lazymc.server.send_proxy_v2"),
+        };
+
+        let time_section: TimeSection = TimeSection {
+            sleep_after: labels.int("lazymc.time.sleep_after"),
+            minimum_online_time: labels.int("lazymc.time.minimum_online_time"),
+        };
+
+        let join_kick_section: JoinKickSection = JoinKickSection {
+            starting: labels.string("lazymc.join.kick.starting"),
+            stopping: labels.string("lazymc.join.kick.stopping"),
+        };
+
+        let join_hold_section: JoinHoldSection = JoinHoldSection {
+            timeout: labels.int("lazymc.join.hold.timeout"),
+        };
+
+        let join_forward_section: JoinForwardSection = JoinForwardSection {
+            address: labels.string("lazymc.join.forward.address"),
+            send_proxy_v2: labels.bool("lazymc.join.forward.send_proxy_v2"),
+        };
+
+        let join_lobby_section: JoinLobbySection = JoinLobbySection {
+            timeout: labels.int("lazymc.join.lobby.timeout"),
+            message: labels.string("lazymc.join.lobby.message"),
+            ready_sound: labels.string("lazymc.join.lobby.ready_sound"),
+        };
+
+        let join_section: JoinSection = JoinSection {
+            methods: labels.list("lazymc.join.methods"),
+            kick: join_kick_section.clone(),
+            hold: join_hold_section.clone(),
+            forward: join_forward_section.clone(),
+            lobby: join_lobby_section.clone(),
+        };
+
+        let public_section: PublicSection = PublicSection {
+            address: Some(format!("0.0.0.0:{}", labels.string("lazymc.port").unwrap())),
+            version: labels.string("lazymc.public.version"),
+            protocol: labels.int("lazymc.public.protocol"),
+        };
+
+        let motd_section: MotdSection = MotdSection {
+            sleeping: labels.string("lazymc.motd.sleeping"),
+            starting: labels.string("lazymc.motd.starting"),
+            stopping: labels.string("lazymc.motd.stopping"),
+            from_server: labels.bool("lazymc.motd.from_server"),
+        };
+
+        let lockout_section: LockoutSection = LockoutSection {
+            enabled: labels.bool("lazymc.lockout.enabled"),
+            message: labels.string("lazymc.lockout.message"),
+        };
+
+        let advanced_section: AdvancedSection = AdvancedSection {
+            rewrite_server_properties: Some(false),
+        };
+
+        let config_section: ConfigSection = ConfigSection {
+            version: Some(version),
+        };
+
+        let notifiers: Notifiers = Notifiers::new(
+            labels.string("lazymc.notify.webhook.url"),
+            labels.string("lazymc.notify.webhook.template"),
+            labels.string("lazymc.notify.discord.url"),
+            labels.string("lazymc.notify.script"),
+            labels.list("lazymc.notify.events"),
+        );
+
+        let mut sources: BTreeMap<String, String> = BTreeMap::new();
+        for label in LABELS {
+            if let (Some(key), Some(source)) = (label.key(), labels.source(label.name)) {
+                sources.insert(key.to_string(), source);
+            }
+        }
+        for key in FIXED_KEYS {
+            sources.insert(key.to_string(), "set by lazymc-docker-proxy".to_string());
+        }
+        sources.insert(
+            "config.version".to_string(),
+            format!("environment variable {}", version_var),
+        );
+        if resolved_ip {
+            sources.insert(
+                "server.address".to_string(),
+                "label lazymc.server.address, resolved to an IP address".to_string(),
+            );
+        }
+
+        Ok(Config {
+            server: server_section,
+            public: public_section,
+            time: time_section,
+            join: join_section,
+            motd: motd_section,
+            lockout: lockout_section,
+            advanced: advanced_section,
+            config: config_section,
+            start_command: match legacy {
+                true => "lazymc-legacy".to_string(),
+                false => "lazymc".to_string(),
+            },
+            config_file: format!("lazymc.{}.toml", group),
+            group,
+            resolved_ip,
+            sources,
+            notifiers,
+        })
+    }
+
+    /// Create a new configuration from environment variables
+    ///
+    /// # Deprecated
+    #[deprecated(since = "2.1.0", note = "Use `from_container_labels` instead")]
+    pub fn from_env() -> Self {
+        warn!(target: "lazymc-docker-proxy::entrypoint::config", "***************************************************************************************************************");
+        warn!(target: "lazymc-docker-proxy::entrypoint::config", "DEPRECATED: Using Environment Variables to configure lazymc is deprecated. Please use container labels instead.");
+        warn!(target: "lazymc-docker-proxy::entrypoint::config", "       see: https://github.com/joesturge/lazymc-docker-proxy?tab=readme-ov-file#usage");
+        warn!(target: "lazymc-docker-proxy::entrypoint::config", "***************************************************************************************************************");
+
+        let mut labels: HashMap<String, String> = HashMap::new();
+        labels.insert("lazymc.enabled".to_string(), "true".to_string());
+        if let Ok(value) = var("LAZYMC_GROUP") {
+            labels.insert("lazymc.group".to_string(), value.clone());
+            // Stop the server container if it is running
+            if let Err(err) = backend::runtime().block_on(backend::stop(&value)) {
+                error!(target: "lazymc-docker-proxy::entrypoint::config", group = value.as_str(); "Failed to stop containers for group {}: {}", value, err);
+            }
+        }
+        if let Ok(value) = var("LAZYMC_JOIN_METHODS") {
+            labels.insert("lazymc.join.methods".to_string(), value);
+        }
+        if let Ok(value) = var("LAZYMC_JOIN_KICK_STARTING") {
+            labels.insert("lazymc.join.kick.starting".to_string(), value);
+        }
+        if let Ok(value) = var("LAZYMC_JOIN_KICK_STOPPING") {
+            labels.insert("lazymc.join.kick.stopping".to_string(), value);
+        }
+        if let Ok(value) = var("LAZYMC_JOIN_HOLD_TIMEOUT") {
+            labels.insert("lazymc.join.hold.timeout".to_string(), value);
+        }
+        if let Ok(value) = var("LAZYMC_JOIN_FORWARD_ADDRESS") {
+            labels.insert("lazymc.join.forward.address".to_string(), value);
+        }
+        if let Ok(value) = var("LAZYMC_JOIN_FORWARD_SEND_PROXY_V2") {
+            labels.insert("lazymc.join.forward.send_proxy_v2".to_string(), value);
+        }
+        if let Ok(value) = var("LAZYMC_JOIN_LOBBY_TIMEOUT") {
+            labels.insert("lazymc.join.lobby.timeout".to_string(), value);
+        }
+        if let Ok(value) = var("LAZYMC_JOIN_LOBBY_MESSAGE") {
+            labels.insert("lazymc.join.lobby.message".to_string(), value);
+        }
+        if let Ok(value) = var("LAZYMC_JOIN_LOBBY_READY_SOUND") {
+            labels.insert("lazymc.join.lobby.ready_sound".to_string(), value);
+        }
+        if let Ok(value) = var("LAZYMC_LOCKOUT_ENABLED") {
+            labels.insert("lazymc.lockout.enabled".to_string(), value);
+        }
+        if let Ok(value) = var("LAZYMC_LOCKOUT_MESSAGE") {
+            labels.insert("lazymc.lockout.message".to_string(), value);
+        }
+        if let Ok(value) = var("LAZYMC_PORT") {
+            labels.insert("lazymc.port".to_string(), value);
+        }
+        if let Ok(value) = var("MOTD_SLEEPING") {
+            labels.insert("lazymc.motd.sleeping".to_string(), value);
+        }
+        if let Ok(value) = var("MOTD_STARTING") {
+            labels.insert("lazymc.motd.starting".to_string(), value);
+        }
+        if let Ok(value) = var("MOTD_STOPPING") {
+            labels.insert("lazymc.motd.stopping".to_string(), value);
+        }
+        if let Ok(value) = var("MOTD_FROM_SERVER") {
+            labels.insert("lazymc.motd.from_server".to_string(), value);
+        }
+        if let Ok(value) = var("PUBLIC_PROTOCOL") {
+            labels.insert("lazymc.public.protocol".to_string(), value);
+        }
+        if let Ok(value) = var("PUBLIC_VERSION") {
+            labels.insert("lazymc.public.version".to_string(), value);
+        }
+        if let Ok(value) = var("SERVER_ADDRESS") {
+            labels.insert("lazymc.server.address".to_string(), value);
+        }
+        if let Ok(value) = var("SERVER_BLOCK_BANNED_IPS") {
+            labels.insert("lazymc.server.block_banned_ips".to_string(), value);
+        }
+        if let Ok(value) = var("SERVER_DIRECTORY") {
+            labels.insert("lazymc.server.directory".to_string(), value);
+        }
+        if let Ok(value) = var("SERVER_DROP_BANNED_IPS") {
+            labels.insert("lazymc.server.drop_banned_ips".to_string(), value);
+        }
+        if let Ok(value) = var("SERVER_FORGE") {
+            labels.insert("lazymc.server.forge".to_string(), value);
+        }
+        if let Ok(value) = var("SERVER_PROBE_ON_START") {
+            labels.insert("lazymc.server.probe_on_start".to_string(), value);
+        }
+        if let Ok(value) = var("SERVER_SEND_PROXY_V2") {
+            labels.insert("lazymc.server.send_proxy_v2".to_string(), value);
+        }
+        if let Ok(value) = var("SERVER_START_TIMEOUT") {
+            labels.insert("lazymc.server.start_timeout".to_string(), value);
+        }
+        if let Ok(value) = var("SERVER_STOP_TIMEOUT") {
+            labels.insert("lazymc.server.stop_timeout".to_string(), value);
+        }
+        if let Ok(value) = var("SERVER_WAKE_WHITELIST") {
+            labels.insert("lazymc.server.wake_whitelist".to_string(), value);
+        }
+        if let Ok(value) = var("TIME_MINIMUM_ONLINE_TIME") {
+            labels.insert("lazymc.time.minimum_online_time".to_string(), value);
+        }
+        if let Ok(value) = var("TIME_SLEEP_AFTER") {
+            labels.insert("lazymc.time.sleep_after".to_string(), value);
+        }
+
+        Config::from_container_labels("environment", labels).unwrap_or_else(|err| {
+            error!(target: "lazymc-docker-proxy::entrypoint::config", "{}", err);
+            health::unhealthy();
+            exit(1);
+        })
+    }
+}
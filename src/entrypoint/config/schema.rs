@@ -0,0 +1,468 @@
+use std::collections::HashMap;
+use std::fmt;
+use version_compare::Version;
+
+use crate::notify;
+
+/// The join methods supported by lazymc
+const JOIN_METHODS: &[&str] = &["kick", "hold", "forward", "lobby"];
+
+/// Join methods which consume the client, so no other method can be used after them
+const CONSUMING_JOIN_METHODS: &[&str] = &["kick", "forward", "lobby"];
+
+/// The signal names docker accepts, without the `SIG` prefix
+const SIGNALS: &[&str] = &[
+    "ABRT", "ALRM", "BUS", "CHLD", "CLD", "CONT", "FPE", "HUP", "ILL", "INT", "IO", "IOT", "KILL",
+    "PIPE", "POLL", "PROF", "PWR", "QUIT", "SEGV", "STKFLT", "STOP", "SYS", "TERM", "TRAP", "TSTP",
+    "TTIN", "TTOU", "URG", "USR1", "USR2", "VTALRM", "WINCH", "XCPU", "XFSZ", "RTMIN", "RTMAX",
+];
+
+/// The highest signal number on linux
+const MAX_SIGNAL: u8 = 64;
+
+/// The type of value a label accepts
+pub enum Kind {
+    /// `true` or `false`
+    Bool,
+    /// A whole number
+    Integer,
+    /// A whole number of seconds, `0` or more
+    Seconds,
+    /// Any text
+    String,
+    /// A port number
+    Port,
+    /// A host and port, such as `mc:25565`
+    Address,
+    /// A minecraft version, such as `1.20.4`
+    Version,
+    /// A signal name or number, such as `SIGINT`
+    Signal,
+    /// A comma separated list of the given values
+    List(&'static [&'static str]),
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Kind::Bool => write!(f, "`true` or `false`"),
+            Kind::Integer => write!(f, "a whole number"),
+            Kind::Seconds => write!(f, "a whole number of seconds, `0` or more"),
+            Kind::String => write!(f, "text"),
+            Kind::Port => write!(f, "a port number"),
+            Kind::Address => write!(f, "a host and port, such as `mc:25565`"),
+            Kind::Version => write!(f, "a minecraft version, such as `1.20.4`"),
+            Kind::Signal => write!(f, "a signal name or number, such as `SIGINT`"),
+            Kind::List(values) => write!(f, "a comma separated list of `{}`", values.join("`, `")),
+        }
+    }
+}
+
+/// Where the value of a label is written in the lazymc config
+pub enum Key {
+    /// The label name without the `lazymc.` prefix
+    Derived,
+    /// A different key
+    Custom(&'static str),
+    /// The label is only used by lazymc-docker-proxy
+    Proxy,
+}
+
+/// A label supported on server containers
+pub struct Label {
+    /// The name of the label
+    pub name: &'static str,
+    /// The type of value the label accepts
+    pub kind: Kind,
+    /// Whether the label must be set
+    pub required: bool,
+    /// The value used when the label is not set
+    pub default: Option<&'static str>,
+    /// The first lazymc version which supports the label
+    pub since: Option<&'static str>,
+    /// Where the value is written in the lazymc config
+    pub key: Key,
+    /// Whether the label is read from each container when it is started or stopped, so it can not be set in
+    /// the configuration file
+    pub per_container: bool,
+}
+
+impl Label {
+    /// Get the key the label is written to in the lazymc config
+    pub fn key(&self) -> Option<&'static str> {
+        match self.key {
+            Key::Derived => self.name.strip_prefix("lazymc."),
+            Key::Custom(key) => Some(key),
+            Key::Proxy => None,
+        }
+    }
+}
+
+/// Shorthand for an optional label without a default
+const fn label(name: &'static str, kind: Kind) -> Label {
+    Label {
+        name,
+        kind,
+        required: false,
+        default: None,
+        since: None,
+        key: Key::Derived,
+        per_container: false,
+    }
+}
+
+/// Every label supported on server containers
+pub const LABELS: &[Label] = &[
+    Label {
+        required: true,
+        key: Key::Proxy,
+        ..label("lazymc.enabled", Kind::Bool)
+    },
+    Label {
+        required: true,
+        key: Key::Proxy,
+        ..label("lazymc.group", Kind::String)
+    },
+    Label {
+        key: Key::Proxy,
+        ..label("lazymc.host", Kind::String)
+    },
+    Label {
+        key: Key::Proxy,
+        ..label("lazymc.proxy", Kind::String)
+    },
+    Label {
+        default: Some("25565"),
+        key: Key::Custom("public.address"),
+        ..label("lazymc.port", Kind::Port)
+    },
+    Label {
+        key: Key::Proxy,
+        per_container: true,
+        ..label("lazymc.start_order", Kind::Integer)
+    },
+    Label {
+        default: Some("false"),
+        key: Key::Proxy,
+        ..label("lazymc.start.wait_for_health", Kind::Bool)
+    },
+    Label {
+        default: Some("false"),
+        key: Key::Proxy,
+        ..label("lazymc.adopt", Kind::Bool)
+    },
+    Label {
+        key: Key::Proxy,
+        per_container: true,
+        ..label("lazymc.stop.timeout", Kind::Seconds)
+    },
+    Label {
+        key: Key::Proxy,
+        per_container: true,
+        ..label("lazymc.stop.signal", Kind::Signal)
+    },
+    Label {
+        key: Key::Proxy,
+        per_container: true,
+        ..label("lazymc.rcon.enabled", Kind::Bool)
+    },
+    Label {
+        key: Key::Proxy,
+        per_container: true,
+        ..label("lazymc.rcon.address", Kind::Address)
+    },
+    Label {
+        key: Key::Proxy,
+        per_container: true,
+        ..label("lazymc.rcon.password", Kind::String)
+    },
+    Label {
+        key: Key::Proxy,
+        ..label("lazymc.notify.webhook.url", Kind::String)
+    },
+    Label {
+        key: Key::Proxy,
+        ..label("lazymc.notify.webhook.template", Kind::String)
+    },
+    Label {
+        key: Key::Proxy,
+        ..label("lazymc.notify.discord.url", Kind::String)
+    },
+    Label {
+        key: Key::Proxy,
+        ..label("lazymc.notify.script", Kind::String)
+    },
+    Label {
+        key: Key::Proxy,
+        ..label("lazymc.notify.events", Kind::List(notify::EVENTS))
+    },
+    label("lazymc.public.version", Kind::Version),
+    label("lazymc.public.protocol", Kind::Integer),
+    Label {
+        required: true,
+        ..label("lazymc.server.address", Kind::Address)
+    },
+    Label {
+        default: Some("/server"),
+        ..label("lazymc.server.directory", Kind::String)
+    },
+    label("lazymc.server.wake_whitelist", Kind::Bool),
+    label("lazymc.server.block_banned_ips", Kind::Bool),
+    label("lazymc.server.drop_banned_ips", Kind::Bool),
+    label("lazymc.server.probe_on_start", Kind::Bool),
+    label("lazymc.server.forge", Kind::Bool),
+    Label {
+        default: Some("false"),
+        ..label("lazymc.server.freeze_process", Kind::Bool)
+    },
+    label("lazymc.server.start_timeout", Kind::Integer),
+    label("lazymc.server.stop_timeout", Kind::Integer),
+    Label {
+        since: Some("0.2.6"),
+        ..label("lazymc.server.send_proxy_v2", Kind::Bool)
+    },
+    label("lazymc.time.sleep_after", Kind::Integer),
+    label("lazymc.time.minimum_online_time", Kind::Integer),
+    label("lazymc.join.methods", Kind::List(JOIN_METHODS)),
+    label("lazymc.join.kick.starting", Kind::String),
+    label("lazymc.join.kick.stopping", Kind::String),
+    label("lazymc.join.hold.timeout", Kind::Integer),
+    label("lazymc.join.forward.address", Kind::Address),
+    Label {
+        since: Some("0.2.6"),
+        ..label("lazymc.join.forward.send_proxy_v2", Kind::Bool)
+    },
+    label("lazymc.join.lobby.timeout", Kind::Integer),
+    label("lazymc.join.lobby.message", Kind::String),
+    label("lazymc.join.lobby.ready_sound", Kind::String),
+    label("lazymc.motd.sleeping", Kind::String),
+    label("lazymc.motd.starting", Kind::String),
+    label("lazymc.motd.stopping", Kind::String),
+    label("lazymc.motd.from_server", Kind::Bool),
+    label("lazymc.lockout.enabled", Kind::Bool),
+    label("lazymc.lockout.message", Kind::String),
+];
+
+/// Find a label in the schema
+fn find(name: &str) -> Option<&'static Label> {
+    LABELS.iter().find(|label| label.name == name)
+}
+
+/// Check whether a label can only be set on the container itself
+pub fn per_container(name: &str) -> bool {
+    find(name).is_some_and(|label| label.per_container)
+}
+
+/// A problem found with the labels of a container
+pub struct Problem {
+    pub label: String,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.label, self.message)
+    }
+}
+
+/// Check a value against the type of a label
+fn check(kind: &Kind, value: &str) -> bool {
+    match kind {
+        Kind::Bool => value == "true" || value == "false",
+        Kind::Integer => value.parse::<i32>().is_ok(),
+        Kind::Seconds => value.parse::<i32>().is_ok_and(|seconds| seconds >= 0),
+        Kind::String => true,
+        Kind::Port => value.parse::<u16>().is_ok_and(|port| port > 0),
+        Kind::Address => value
+            .rsplit_once(':')
+            .is_some_and(|(host, port)| !host.is_empty() && check(&Kind::Port, port)),
+        Kind::Version => {
+            value.starts_with(|c: char| c.is_ascii_digit()) && Version::from(value).is_some()
+        }
+        Kind::List(values) => value.split(',').all(|item| values.contains(&item.trim())),
+        Kind::Signal => check_signal(value),
+    }
+}
+
+/// Check a signal the way docker parses it, by number or by name with an optional `SIG` prefix and
+/// `RTMIN+n` or `RTMAX-n` for the real-time signals
+fn check_signal(value: &str) -> bool {
+    if let Ok(number) = value.parse::<u8>() {
+        return (1..=MAX_SIGNAL).contains(&number);
+    }
+    let name: String = value.to_uppercase();
+    let name: &str = name.strip_prefix("SIG").unwrap_or(&name);
+    let offset = |name: &str, prefix: &str, sign: char| {
+        name.strip_prefix(prefix)
+            .and_then(|offset| offset.strip_prefix(sign))
+            .and_then(|offset| offset.parse::<u8>().ok())
+            .is_some_and(|offset| (1..=15).contains(&offset))
+    };
+    SIGNALS.contains(&name) || offset(name, "RTMIN", '+') || offset(name, "RTMAX", '-')
+}
+
+/// Labels of a container which were validated against the schema
+pub struct Labels {
+    values: HashMap<String, String>,
+    /// Where values which were not set by a container label came from
+    origins: HashMap<String, String>,
+}
+
+impl Labels {
+    /// Validate the labels of a container, reporting every problem at once
+    ///
+    /// Unknown `lazymc.*` labels are logged as warnings, they are usually typos.
+    pub fn validate(
+        container: &str,
+        lazymc_version: Option<&str>,
+        values: HashMap<String, String>,
+    ) -> Result<Self, Vec<Problem>> {
+        let mut problems: Vec<Problem> = Vec::new();
+        let problem = |label: &str, message: String| Problem {
+            label: label.to_string(),
+            message,
+        };
+
+        for (name, value) in values.iter() {
+            if !name.starts_with("lazymc.") {
+                continue;
+            }
+
+            let Some(label) = find(name) else {
+                warn!(target: "lazymc-docker-proxy::entrypoint::config", "Unknown label {} on container {} will be ignored", name, container);
+                continue;
+            };
+
+            if !check(&label.kind, value) {
+                problems.push(problem(
+                    name,
+                    format!("expected {}, got `{}`", label.kind, value),
+                ));
+            }
+
+            if let (Some(since), Some(lazymc_version)) = (label.since, lazymc_version) {
+                if Version::from(lazymc_version) < Version::from(since) {
+                    warn!(target: "lazymc-docker-proxy::entrypoint::config", "Label {} on container {} requires lazymc {} or newer, but lazymc {} is used", name, container, since, lazymc_version);
+                }
+            }
+        }
+
+        for label in LABELS.iter().filter(|label| label.required) {
+            if !values.contains_key(label.name) {
+                problems.push(problem(label.name, "required label is not set".to_string()));
+            }
+        }
+
+        let labels: Labels = Labels {
+            values,
+            origins: HashMap::new(),
+        };
+        if problems.is_empty() {
+            labels.validate_join(container, &mut problems);
+        }
+
+        match problems.is_empty() {
+            true => Ok(labels),
+            false => Err(problems),
+        }
+    }
+
+    /// Record where values which were not set by a container label came from
+    pub fn with_origins(mut self, origins: HashMap<String, String>) -> Self {
+        self.origins = origins;
+        self
+    }
+
+    /// Validate that the join method labels fit together
+    fn validate_join(&self, container: &str, problems: &mut Vec<Problem>) {
+        let methods: Vec<String> = self.list("lazymc.join.methods").unwrap_or_default();
+
+        // labels for a join method are only used when the method is enabled
+        for method in JOIN_METHODS {
+            let prefix: &String = &format!("lazymc.join.{}.", method);
+            let configured: bool = self.values.keys().any(|name| name.starts_with(prefix));
+            if configured && !methods.iter().any(|enabled| enabled == method) {
+                warn!(target: "lazymc-docker-proxy::entrypoint::config", "Labels {}* on container {} have no effect unless `{}` is in lazymc.join.methods", prefix, container, method);
+            }
+        }
+
+        // methods listed after one that consumes the client are never used
+        if let Some(position) = methods
+            .iter()
+            .position(|method| CONSUMING_JOIN_METHODS.contains(&method.as_str()))
+        {
+            if position + 1 < methods.len() {
+                warn!(target: "lazymc-docker-proxy::entrypoint::config", "Join method `{}` on container {} consumes the client, methods after it in lazymc.join.methods are never used", methods[position], container);
+            }
+        }
+
+        if methods.iter().any(|method| method == "forward")
+            && self.string("lazymc.join.forward.address").is_none()
+        {
+            problems.push(Problem {
+                label: "lazymc.join.forward.address".to_string(),
+                message: "required when `forward` is in lazymc.join.methods".to_string(),
+            });
+        }
+
+        if methods.iter().any(|method| method == "lobby") {
+            if self
+                .int("lazymc.join.lobby.timeout")
+                .is_some_and(|timeout| timeout <= 0)
+            {
+                problems.push(Problem {
+                    label: "lazymc.join.lobby.timeout".to_string(),
+                    message: "must be greater than 0 when `lobby` is in lazymc.join.methods"
+                        .to_string(),
+                });
+            }
+            if self.bool("lazymc.server.forge") == Some(true) {
+                warn!(target: "lazymc-docker-proxy::entrypoint::config", "Join method `lobby` on container {} may not work with forge servers", container);
+            }
+        }
+    }
+
+    /// Get the value of a label, or its default
+    fn value(&self, name: &str) -> Option<&str> {
+        let label: &Label = find(name).unwrap_or_else(|| panic!("{} is not in the schema", name));
+        self.values.get(name).map(String::as_str).or(label.default)
+    }
+
+    /// Describe where the value of a label came from, if it has one
+    pub fn source(&self, name: &str) -> Option<String> {
+        let label: &Label = find(name).unwrap_or_else(|| panic!("{} is not in the schema", name));
+        match (self.values.contains_key(name), label.default) {
+            (true, _) => Some(match self.origins.get(name) {
+                Some(origin) => format!("{} in {}", name, origin),
+                None => format!("label {}", name),
+            }),
+            (false, Some(_)) => Some(format!("default of {}", name)),
+            (false, None) => None,
+        }
+    }
+
+    /// Get the value of a text label
+    pub fn string(&self, name: &str) -> Option<String> {
+        self.value(name).map(str::to_string)
+    }
+
+    /// Get the value of a `true` or `false` label
+    pub fn bool(&self, name: &str) -> Option<bool> {
+        self.value(name).map(|value| value == "true")
+    }
+
+    /// Get the value of a whole number label
+    pub fn int(&self, name: &str) -> Option<i32> {
+        self.value(name).and_then(|value| value.parse().ok())
+    }
+
+    /// Get the value of a comma separated list label
+    pub fn list(&self, name: &str) -> Option<Vec<String>> {
+        self.value(name).map(|value| {
+            value
+                .split(',')
+                .map(|item| item.trim().to_string())
+                .collect()
+        })
+    }
+}
@@ -1,80 +1,294 @@
-mod config;
+pub mod config;
+mod supervisor;
 use config::Config;
 use log::Level;
 use regex::Regex;
 use std::{
-    io::{BufRead, BufReader},
-    process::{self, exit},
-    sync::OnceLock,
+    collections::{BTreeSet, HashMap, HashSet},
+    env::var,
+    process::exit,
+    sync::{mpsc, Mutex, OnceLock},
+    thread,
+    time::Duration,
 };
+use supervisor::Group;
 
 use crate::{
-    docker,
+    backend,
+    events::{self, Event, State},
     health::{self},
+    http, logging, metrics, notify,
 };
 
+/// How often the lazymc processes are checked when no container events are received
+const SUPERVISE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often the API of the backend is checked to be reachable
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+
 /// Entrypoint for the application
 pub fn run() {
-    // Ensure all server containers are stopped before starting
+    // Serve the health and status endpoints if enabled
+    http::start();
+
+    // Fail clearly if the API of the backend can not be reached, such as a remote docker daemon which is down
+    if let Err(err) = backend::runtime().block_on(backend::ping()) {
+        error!(target: "lazymc-docker-proxy::entrypoint", "Failed to reach the {} API: {}", backend::get(), err);
+        health::set_backend_error(Some(err.to_string()));
+        health::unhealthy();
+        exit(1);
+    }
+    health::set_backend_error(None);
+
+    // Ensure the server containers of groups which are not adopted are stopped before starting
     info!(target: "lazymc-docker-proxy::entrypoint", "Ensuring all server containers are stopped...");
-    docker::stop_all_containers();
+    let adopted: HashSet<String> = match stop_servers() {
+        Ok(adopted) => adopted
+            .into_iter()
+            .inspect(|group| info!(target: "lazymc-docker-proxy::entrypoint", group = group.as_str(); "Adopting the running server of group {}", group))
+            .collect(),
+        Err(err) => {
+            error!(target: "lazymc-docker-proxy::entrypoint", "Failed to stop server containers: {}", err);
+            health::unhealthy();
+            exit(1);
+        }
+    };
 
-    let labels_list = docker::get_container_labels();
-    let mut configs: Vec<Config> = Vec::new();
-    let mut children: Vec<process::Child> = Vec::new();
+    let mut groups: HashMap<String, Group> = HashMap::new();
+    let mut validated: HashMap<String, Validated> = HashMap::new();
 
-    for label in labels_list {
-        configs.push(Config::from_container_labels(label));
-    }
+    // If this app receives a signal, stop the server containers of groups which are not adopted
+    ctrlc::set_handler(move || {
+        info!(target: "lazymc-docker-proxy::entrypoint", "Received exit signal. Stopping all server containers...");
+        if let Err(err) = stop_servers() {
+            error!(target: "lazymc-docker-proxy::entrypoint", "Failed to stop server containers: {}", err);
+            exit(1);
+        }
+        exit(0);
+    }).unwrap();
 
-    if configs.is_empty() {
-        configs.push(Config::from_env());
-    }
+    let (sender, receiver) = mpsc::channel::<backend::ContainerEvent>();
+
+    // Fall back to the deprecated environment variable configuration if no containers are labelled
+    let labelled: bool = backend::runtime()
+        .block_on(backend::get_labels())
+        .map_or(true, |containers| !containers.is_empty());
+    if !labelled && var("LAZYMC_GROUP").is_ok() {
+        // containers are not watched when configured by environment variables
+        #[allow(deprecated)]
+        let config: Config = Config::from_env();
+        groups.insert(config.group().into(), Group::start(config));
+    } else {
+        reconcile(&mut groups, &mut validated, &adopted);
+        if groups.is_empty() {
+            info!(target: "lazymc-docker-proxy::entrypoint", "No containers with the label lazymc.enabled=true found, waiting for containers to be created...");
+        }
 
-    for config in configs {
-        let group: String = config.group().into();
-
-        info!(target: "lazymc-docker-proxy::entrypoint", "Starting lazymc process for group: {}...", group.clone());
-        let mut child: process::Child = config
-            .start_command()
-            .stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::piped())
-            .spawn()
-            .unwrap();
-
-        let mut stdout = child.stdout.take();
-        let group_clone = group.clone();
-        std::thread::spawn(move || {
-            let stdout_reader = BufReader::new(stdout.take().unwrap());
-            for line in stdout_reader.lines() {
-                wrap_log(&group_clone, line);
+        // Watch for containers being created, destroyed, renamed, started or changing networks
+        let sender = sender.clone();
+        thread::spawn(move || loop {
+            if let Err(err) = backend::runtime().block_on(backend::watch(sender.clone())) {
+                error!(target: "lazymc-docker-proxy::entrypoint", "Error watching {} events: {}", backend::get(), err);
             }
-        });
+            warn!(target: "lazymc-docker-proxy::entrypoint", "The {} event stream ended, reconnecting in 5 seconds...", backend::get());
+            thread::sleep(Duration::from_secs(5));
 
-        let mut stderr = child.stderr.take();
-        std::thread::spawn(move || {
-            let stderr_reader = BufReader::new(stderr.take().unwrap());
-            for line in stderr_reader.lines() {
-                wrap_log(&group.clone(), line)
+            // events were missed while the stream was down, so reconcile once it is back
+            let reconnect = backend::ContainerEvent {
+                action: "reconnect".to_string(),
+                group: None,
+            };
+            if sender.send(reconnect).is_err() {
+                return;
             }
         });
-
-        children.push(child);
     }
 
-    // If this app receives a signal, stop all server containers
-    ctrlc::set_handler(move || {
-        info!(target: "lazymc-docker-proxy::entrypoint", "Received exit signal. Stopping all server containers...");
-        docker::stop_all_containers();
-        exit(0);
-    }).unwrap();
-
     // Set the health status to healthy
     health::healthy();
 
-    // wait indefinitely
+    // Report the application as unhealthy while the API of the backend can not be reached
+    thread::spawn(|| loop {
+        thread::sleep(PING_INTERVAL);
+        let result = backend::runtime().block_on(backend::ping());
+        health::set_backend_error(result.err().map(|err| err.to_string()));
+    });
+
     loop {
-        std::thread::park();
+        if let Ok(event) = receiver.recv_timeout(SUPERVISE_INTERVAL) {
+            debug!(target: "lazymc-docker-proxy::entrypoint", "Received container {} event for group: {}", event.action, event.group.clone().unwrap_or_default());
+
+            // A renamed container may resolve to a different address, so start its group afresh
+            if event.action == "rename" {
+                if let Some(group) = event.group.and_then(|group| groups.remove(&group)) {
+                    group.stop();
+                }
+            }
+
+            reconcile(&mut groups, &mut validated, &HashSet::new());
+        }
+
+        // Restart any lazymc processes which have exited
+        for group in groups.values_mut() {
+            group.supervise();
+        }
+    }
+}
+
+/// The merged labels a container was last validated with, and its configuration or the group of its invalid labels
+type Validated = (HashMap<String, String>, Result<Config, Option<String>>);
+
+/// Find the groups of labelled containers with the label "lazymc.adopt=true", whose running servers are left
+/// running when the proxy starts or exits, with the names of their servers
+fn adoption() -> Result<HashMap<String, HashSet<String>>, backend::Error> {
+    let mut adopted: HashMap<String, HashSet<String>> = HashMap::new();
+    let mut stopped: HashSet<String> = HashSet::new();
+    for container in backend::runtime().block_on(backend::get_labels())? {
+        let Some(group) = container.labels.get("lazymc.group").cloned() else {
+            continue;
+        };
+        // labels which can not be merged with the configuration file are reported when the group is started
+        let adopt: bool = config::file::merge(container.labels).is_ok_and(|merged| {
+            merged.labels.get("lazymc.adopt").map(String::as_str) == Some("true")
+        });
+        match adopt {
+            true => {
+                adopted.entry(group).or_default().insert(container.name);
+            }
+            false => {
+                stopped.insert(group);
+            }
+        }
+    }
+
+    // a group is only adopted if every one of its servers is
+    adopted.retain(|group, _| !stopped.contains(group));
+    Ok(adopted)
+}
+
+/// Stop the containers of every group except the adopted groups whose server is running, returning those groups
+///
+/// Containers which only support a server, and those of groups without a labelled server, are only found by
+/// stopping every group.
+fn stop_servers() -> Result<HashSet<String>, backend::Error> {
+    let adopted: HashSet<String> = adoption()?
+        .into_iter()
+        .filter(|(group, servers)| running(group, servers))
+        .map(|(group, _)| group)
+        .collect();
+
+    backend::runtime()
+        .block_on(backend::stop_all(&adopted))
+        .map(|_| adopted)
+}
+
+/// Whether one of the given servers of a group is running, ignoring the containers which only support them
+fn running(group: &str, servers: &HashSet<String>) -> bool {
+    backend::runtime()
+        .block_on(backend::get_states(group))
+        .is_ok_and(|states| {
+            states
+                .iter()
+                .any(|(name, state)| servers.contains(name) && state == "running")
+        })
+}
+
+/// Start, stop and restart lazymc processes so that there is one for each group of labelled containers,
+/// configured with the current address of the server
+///
+/// lazymc is started for the given adopted groups with their server considered online.
+fn reconcile(
+    groups: &mut HashMap<String, Group>,
+    validated: &mut HashMap<String, Validated>,
+    adopted: &HashSet<String>,
+) {
+    let mut configs: HashMap<String, Config> = HashMap::new();
+    let mut invalid: HashSet<String> = HashSet::new();
+
+    // keep the current groups running if the containers could not be listed
+    let containers: Vec<backend::ContainerLabels> = match backend::runtime()
+        .block_on(backend::get_labels())
+    {
+        Ok(containers) => containers,
+        Err(err) => {
+            error!(target: "lazymc-docker-proxy::entrypoint", "Failed to get container labels: {}", err);
+            return;
+        }
+    };
+
+    // Only validate containers which are new or whose labels changed, so their warnings are logged once
+    validated.retain(|name, _| containers.iter().any(|container| &container.name == name));
+    for container in containers {
+        let merged: HashMap<String, String> = config::file::merge(container.labels.clone())
+            .map_or_else(|_| container.labels.clone(), |merged| merged.labels);
+        let result: Result<Config, Option<String>> = match validated.get(&container.name) {
+            Some((labels, result)) if *labels == merged => result.clone(),
+            _ => {
+                let result = Config::from_container_labels(&container.name, container.labels)
+                    .map_err(|err| {
+                        error!(target: "lazymc-docker-proxy::entrypoint", "{}", err);
+                        err.group
+                    });
+                validated.insert(container.name, (merged, result.clone()));
+                result
+            }
+        };
+
+        match result {
+            Ok(config) => {
+                configs.insert(config.group().into(), config);
+            }
+            Err(Some(group)) => {
+                health::update_group(&group, |health| health.config_valid = false);
+                invalid.insert(group);
+            }
+            Err(None) => {}
+        }
+    }
+
+    // Stop lazymc for groups which no longer have a container
+    let removed: Vec<String> = groups
+        .keys()
+        .filter(|group| !configs.contains_key(*group))
+        .cloned()
+        .collect();
+    for group in removed {
+        info!(target: "lazymc-docker-proxy::entrypoint", group = group.as_str(); "Container for group {} was removed", group);
+        groups.remove(&group).unwrap().stop();
+    }
+
+    // Forget the health of groups whose containers were removed, including those with invalid labels
+    for group in health::read().groups.into_keys() {
+        if !configs.contains_key(&group)
+            && !invalid.contains(&group)
+            && !groups.contains_key(&group)
+        {
+            health::remove_group(&group);
+        }
+    }
+
+    for (group, config) in configs {
+        match groups.get(&group) {
+            // Restart lazymc if the server is now reachable at a different address, as lazymc
+            // does not expect the address of a server to change
+            Some(running)
+                if config.resolved_ip()
+                    && config.server_address() != running.config().server_address() =>
+            {
+                info!(target: "lazymc-docker-proxy::entrypoint", group = group.as_str(); "Address for group {} changed from {} to {}", group, running.config().server_address().unwrap_or_default(), config.server_address().unwrap_or_default());
+                groups.remove(&group).unwrap().stop();
+                groups.insert(group, Group::start(config.wake_on_start()));
+            }
+            // notifiers do not affect lazymc, so pick up changes to them without a restart
+            Some(_) => notify::register(&group, config.notifiers().clone()),
+            // Start lazymc for groups which do not have a process yet
+            None if adopted.contains(&group) => {
+                groups.insert(group, Group::start(config.wake_on_start()));
+            }
+            None => {
+                groups.insert(group, Group::start(config));
+            }
+        }
     }
 }
 
@@ -94,21 +308,154 @@ fn wrap_log(group: &String, line: Result<String, std::io::Error>) {
 
             let wrapped_target = &format!("{}::{}", group, target);
             let log_message = message.to_string();
-            log!(target: wrapped_target, level, "{}", log_message);
-            handle_log(group, &level, &log_message);
+            log!(target: wrapped_target, level, group = group.as_str(); "{}", log_message);
+            if let Some(event) = events::parse(level, target, &log_message) {
+                handle_event(group, event);
+            }
         } else {
-            print!("{}", line);
+            if logging::json() {
+                info!(target: &format!("{}::lazymc", group), group = group.as_str(); "{}", line);
+            } else {
+                print!("{}", line);
+            }
+            if let Some(event) = events::parse(Level::Info, "", line.trim_end()) {
+                handle_event(group, event);
+            }
+        }
+    }
+}
+
+/// Handle the lifecycle events of a server
+fn handle_event(group: &str, event: Event) {
+    debug!(target: "lazymc-docker-proxy::entrypoint", group = group; "Received {:?} event for group: {}", event, group);
+
+    let (server, previous) = events::apply(group, &event);
+    if let Some(previous) = previous {
+        debug!(target: "lazymc-docker-proxy::entrypoint", group = group; "Server for group {} went from {} to {}", group, previous, server.state);
+    }
+    health::update_group(group, |health| {
+        health.server = server.state;
+        health.players = server.players;
+    });
+
+    match event {
+        Event::Starting { player } => {
+            metrics::wake(group);
+            let message: String = match &player {
+                Some(player) => format!("{} woke the server for group {}", player, group),
+                None => format!("Server for group {} is starting", group),
+            };
+            notify::send(group, notify::Kind::Wake, message, player);
+        }
+        Event::Online => {
+            metrics::online(group);
+            follow_players(group, server.started.unwrap_or_else(health::now));
+            notify::send(
+                group,
+                notify::Kind::Online,
+                format!("Server for group {} is online", group),
+                None,
+            );
+        }
+        // lazymc announces that it is stopping an idle server, so a server which goes straight from online to
+        // sleeping has stopped by itself
+        Event::Sleeping if previous == Some(State::Online) => {
+            metrics::sleep(group);
+            notify::send(
+                group,
+                notify::Kind::Crash,
+                format!("Server for group {} stopped unexpectedly", group),
+                None,
+            );
         }
+        Event::Sleeping => {
+            metrics::sleep(group);
+            notify::send(
+                group,
+                notify::Kind::Sleep,
+                format!("Server for group {} is sleeping", group),
+                None,
+            );
+        }
+        Event::StopFailed => {
+            warn!(target: "lazymc-docker-proxy::entrypoint", group = group; "Unexpected server state detected, force stopping {} server container...", group);
+            metrics::forced_stop(group);
+            let message: String = match backend::runtime().block_on(backend::stop(group)) {
+                Ok(()) => {
+                    info!(target: "lazymc-docker-proxy::entrypoint", group = group; "{} server container forcefully stopped", group);
+                    format!("Server for group {} was forcefully stopped", group)
+                }
+                Err(err) => {
+                    error!(target: "lazymc-docker-proxy::entrypoint", group = group; "Failed to force stop {} server container: {}", group, err);
+                    format!(
+                        "Server for group {} could not be forcefully stopped: {}",
+                        group, err
+                    )
+                }
+            };
+            notify::send(group, notify::Kind::ForcedStop, message, None);
+        }
+        Event::ContainersStarted => health::record_start(group, true, true),
+        Event::ContainersNotFound => health::record_start(group, false, true),
+        Event::StartFailed(reason) => {
+            health::record_start(group, true, false);
+            notify::send(
+                group,
+                notify::Kind::StartFailed,
+                format!("Server for group {} failed to start: {}", group, reason),
+                None,
+            );
+        }
+        Event::StartTimedOut => {
+            notify::send(
+                group,
+                notify::Kind::StartFailed,
+                format!("Server for group {} did not come online in time", group),
+                None,
+            );
+        }
+        Event::PlayerJoined(player) => {
+            info!(target: "lazymc-docker-proxy::entrypoint", group = group; "{} joined the server for group {}", player, group);
+        }
+        Event::PlayerLeft(player) => {
+            info!(target: "lazymc-docker-proxy::entrypoint", group = group; "{} left the server for group {}", player, group);
+        }
+        Event::KickedWhileStarting(player) => {
+            info!(target: "lazymc-docker-proxy::entrypoint", group = group; "{} was kicked while the server for group {} was starting", player, group);
+            metrics::kick(group);
+        }
+        Event::DockerError => metrics::docker_error(group),
+        Event::Stopping => {}
     }
 }
 
-/// Handle log messages that require special attention
-fn handle_log(group: &str, level: &Level, message: &str) {
-    if let (Level::Warn, "Failed to stop server, no more suitable stopping method to use") =
-        (level, message)
+/// Follow the output of the servers of an online group since the given time, in seconds since the unix epoch, for the
+/// players joining and leaving, unless it is already followed
+///
+/// The output is followed until the servers stop.
+fn follow_players(group: &str, since: u64) {
+    static FOLLOWING: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+    if !FOLLOWING
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .insert(group.to_string())
     {
-        warn!(target: "lazymc-docker-proxy::entrypoint", "Unexpected server state detected, force stopping {} server container...", group);
-        docker::stop(group.to_string());
-        info!(target: "lazymc-docker-proxy::entrypoint", "{} server container forcefully stopped", group);
+        return;
     }
+
+    let group: String = group.to_string();
+    thread::spawn(move || {
+        let result = backend::runtime().block_on(backend::follow_logs(&group, since, |line| {
+            if let Some(event) = events::parse_server(line) {
+                handle_event(&group, event);
+            }
+        }));
+        if let Err(err) = result {
+            warn!(target: "lazymc-docker-proxy::entrypoint", group = group.as_str(); "Failed to follow the output of the server for group {}: {}", group, err);
+        }
+        FOLLOWING
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .remove(&group);
+    });
 }
@@ -0,0 +1,191 @@
+use std::{
+    io::{BufRead, BufReader},
+    process::{self, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::{config::Config, wrap_log};
+use crate::{events, health, metrics, notify};
+
+/// Delay before restarting a lazymc process after its first failure, doubled after each consecutive failure
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Maximum delay before restarting a lazymc process
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// A lazymc process which ran for at least this long is considered to have started successfully
+const STABLE_AFTER: Duration = Duration::from_secs(60);
+
+/// Number of consecutive failures after which a group is considered degraded
+const DEGRADED_AFTER: u32 = 5;
+
+/// A supervised lazymc process and the configuration it was started with
+pub struct Group {
+    config: Config,
+    child: Option<process::Child>,
+    started_at: Instant,
+    restart_at: Option<Instant>,
+    failures: u32,
+    crashes: u32,
+    last_exit: Option<String>,
+}
+
+impl Group {
+    /// Generate the lazymc config file for a group and start a lazymc process for it
+    ///
+    /// The crash history of a group which is restarted, such as when its address changes, is kept.
+    pub fn start(config: Config) -> Self {
+        config.create_file();
+        let previous: Option<health::GroupHealth> = health::read().groups.remove(config.group());
+        health::update_group(config.group(), |health| {
+            health.config_valid = true;
+            health.port = config.port();
+        });
+        metrics::register(config.group());
+        notify::register(config.group(), config.notifiers().clone());
+
+        let mut group: Group = Group {
+            config,
+            child: None,
+            started_at: Instant::now(),
+            restart_at: None,
+            failures: 0,
+            crashes: previous.as_ref().map_or(0, |health| health.crashes),
+            last_exit: previous.and_then(|health| health.last_exit),
+        };
+        group.spawn();
+        group
+    }
+
+    /// Get the configuration the lazymc process was started with
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Spawn the lazymc process and forward its output to the log
+    fn spawn(&mut self) {
+        let group: String = self.config.group().into();
+        self.started_at = Instant::now();
+        self.restart_at = None;
+
+        info!(target: "lazymc-docker-proxy::entrypoint", group = group.as_str(); "Starting lazymc process for group: {}...", group.clone());
+        let mut child: process::Child = match self
+            .config
+            .start_command()
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(err) => {
+                self.failed(format!("failed to start: {}", err));
+                return;
+            }
+        };
+        health::update_group(&group, |health| health.process_running = true);
+
+        let mut stdout = child.stdout.take();
+        let group_clone = group.clone();
+        thread::spawn(move || {
+            let stdout_reader = BufReader::new(stdout.take().unwrap());
+            for line in stdout_reader.lines() {
+                wrap_log(&group_clone, line);
+            }
+        });
+
+        let mut stderr = child.stderr.take();
+        thread::spawn(move || {
+            let stderr_reader = BufReader::new(stderr.take().unwrap());
+            for line in stderr_reader.lines() {
+                wrap_log(&group.clone(), line)
+            }
+        });
+
+        self.child = Some(child);
+    }
+
+    /// Record a failure of the lazymc process and schedule a restart with exponential backoff
+    fn failed(&mut self, reason: String) {
+        self.failures += 1;
+        self.crashes += 1;
+
+        let backoff: Duration = INITIAL_BACKOFF
+            .saturating_mul(2u32.saturating_pow(self.failures - 1))
+            .min(MAX_BACKOFF);
+
+        error!(target: "lazymc-docker-proxy::entrypoint", group = self.config.group(); "lazymc process for group {} {}, restarting in {} seconds...", self.config.group(), reason, backoff.as_secs());
+        if self.failures == DEGRADED_AFTER {
+            warn!(target: "lazymc-docker-proxy::entrypoint", group = self.config.group(); "lazymc process for group {} has failed {} times in a row, group is degraded", self.config.group(), self.failures);
+        }
+
+        self.restart_at = Some(Instant::now() + backoff);
+        notify::send(
+            self.config.group(),
+            notify::Kind::Crash,
+            format!(
+                "lazymc process for group {} {}",
+                self.config.group(),
+                reason
+            ),
+            None,
+        );
+        self.last_exit = Some(reason);
+
+        health::update_group(self.config.group(), |health| {
+            health.process_running = false;
+            health.degraded = self.failures >= DEGRADED_AFTER;
+            health.crashes = self.crashes;
+            health.last_exit = self.last_exit.clone();
+        });
+    }
+
+    /// Check the lazymc process, restarting it once its backoff has elapsed if it has exited
+    pub fn supervise(&mut self) {
+        if let Some(child) = self.child.as_mut() {
+            match child.try_wait() {
+                Ok(None) => {
+                    // the process has been up long enough to no longer be degraded
+                    if self.failures > 0 && self.started_at.elapsed() >= STABLE_AFTER {
+                        info!(target: "lazymc-docker-proxy::entrypoint", group = self.config.group(); "lazymc process for group {} recovered", self.config.group());
+                        self.failures = 0;
+                        health::update_group(self.config.group(), |health| health.degraded = false);
+                    }
+                    return;
+                }
+                Ok(Some(status)) => {
+                    self.child = None;
+                    self.failed(format!("exited with {}", status));
+                }
+                Err(err) => {
+                    self.child = None;
+                    self.failed(format!("could not be waited on: {}", err));
+                }
+            }
+        }
+
+        if self
+            .restart_at
+            .is_some_and(|restart_at| Instant::now() >= restart_at)
+        {
+            metrics::restart(self.config.group());
+            self.spawn();
+        }
+    }
+
+    /// Terminate the lazymc process and remove its config file
+    ///
+    /// The health of the group is kept, it is removed once the group itself is gone.
+    pub fn stop(mut self) {
+        info!(target: "lazymc-docker-proxy::entrypoint", group = self.config.group(); "Stopping lazymc process for group: {}...", self.config.group());
+        if let Some(mut child) = self.child.take() {
+            if let Err(err) = child.kill() {
+                warn!(target: "lazymc-docker-proxy::entrypoint", group = self.config.group(); "Failed to stop lazymc process for group {}: {}", self.config.group(), err);
+            }
+            let _ = child.wait();
+        }
+        self.config.remove_file();
+        events::forget(self.config.group());
+        notify::forget(self.config.group());
+    }
+}
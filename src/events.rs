@@ -0,0 +1,255 @@
+use log::Level;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::{Mutex, OnceLock},
+};
+use strum::Display;
+
+use crate::health::now;
+
+/// A lifecycle event of a server, parsed from the output of its lazymc process or of the server itself
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// lazymc is starting the server, woken by the given player if known
+    Starting { player: Option<String> },
+    /// The server is online
+    Online,
+    /// The server has been idle and lazymc is stopping it
+    Stopping,
+    /// The server is sleeping
+    Sleeping,
+    /// A player joined the server, read from the output of the server itself
+    PlayerJoined(String),
+    /// A player left the server, read from the output of the server itself
+    PlayerLeft(String),
+    /// A player was kicked because the server is still starting
+    KickedWhileStarting(String),
+    /// The server did not come online in time
+    StartTimedOut,
+    /// lazymc had no way left to stop the server
+    StopFailed,
+    /// The start command started the server containers
+    ContainersStarted,
+    /// The start command found no server containers for the group
+    ContainersNotFound,
+    /// The start command failed to start the server containers, for the given reason
+    StartFailed(String),
+    /// The start command failed to call the docker API
+    DockerError,
+}
+
+/// Patterns for the messages which carry an event, matched in order against the message of a log line
+#[allow(clippy::type_complexity)]
+const PATTERNS: &[(&str, fn(Option<String>) -> Event)] = &[
+    (
+        r"^Starting server(?: for '(?P<player>[^']+)')?\.\.\.$",
+        |player| Event::Starting { player },
+    ),
+    (r"^Server is now online", |_| Event::Online),
+    (r"^Server has been idle, sleeping", |_| Event::Stopping),
+    (r"^Server is now sleeping", |_| Event::Sleeping),
+    (
+        r"^Kicked (?:player )?'?(?P<player>[^'\s]+)'?.*\bstarting\b",
+        |player| Event::KickedWhileStarting(player.unwrap_or_default()),
+    ),
+    (
+        r"(?i)\bdid(?:n't| not) start within\b|\bstart(?:ing)? timed out\b",
+        |_| Event::StartTimedOut,
+    ),
+    (
+        r"^Failed to stop server, no more suitable stopping method to use",
+        |_| Event::StopFailed,
+    ),
+];
+
+/// Patterns for the lines of the server output which carry an event
+///
+/// lazymc only proxies the connections of an online server, so players joining and leaving are only seen by the
+/// server, which logs lines such as `[12:00:00] [Server thread/INFO]: Steve joined the game`.
+#[allow(clippy::type_complexity)]
+const SERVER_PATTERNS: &[(&str, fn(String) -> Event)] = &[
+    (
+        r"\]: (?P<player>[A-Za-z0-9_]{1,16}) joined the game$",
+        Event::PlayerJoined,
+    ),
+    (
+        r"\]: (?P<player>[A-Za-z0-9_]{1,16}) left the game$",
+        Event::PlayerLeft,
+    ),
+];
+
+/// Parse a line of lazymc output into an event, the target is empty for lines which are not log messages
+pub fn parse(level: Level, target: &str, message: &str) -> Option<Event> {
+    static REGEXES: OnceLock<Vec<Regex>> = OnceLock::new();
+    let regexes = REGEXES.get_or_init(|| {
+        PATTERNS
+            .iter()
+            .map(|(pattern, _)| Regex::new(pattern).unwrap())
+            .collect()
+    });
+
+    static START_FAILED_REGEX: OnceLock<Regex> = OnceLock::new();
+    let start_failed = START_FAILED_REGEX.get_or_init(|| {
+        Regex::new(r"^(?:Failed to start server for group \S+|Server for group \S+ failed to start): (?P<reason>.+)$")
+            .unwrap()
+    });
+
+    // messages of the start command are not lazymc events
+    if target.starts_with("lazymc-docker-proxy") {
+        return match (level, target) {
+            (Level::Info, "lazymc-docker-proxy::command")
+                if message.starts_with("Started server containers of group ") =>
+            {
+                Some(Event::ContainersStarted)
+            }
+            (Level::Error, "lazymc-docker-proxy::command")
+                if message.starts_with("No server found with the label lazymc.group=") =>
+            {
+                Some(Event::ContainersNotFound)
+            }
+            (Level::Error, "lazymc-docker-proxy::command") => start_failed
+                .captures(message)
+                .map(|captures| Event::StartFailed(captures["reason"].to_string())),
+            (Level::Warn, "lazymc-docker-proxy::metrics")
+                if message.starts_with("Docker API call failed") =>
+            {
+                Some(Event::DockerError)
+            }
+            _ => None,
+        };
+    }
+
+    regexes
+        .iter()
+        .zip(PATTERNS)
+        .find_map(|(regex, (_, event))| {
+            regex.captures(message).map(|captures| {
+                event(
+                    captures
+                        .name("player")
+                        .map(|player| player.as_str().to_string()),
+                )
+            })
+        })
+}
+
+/// Parse a line of the output of a server into an event
+pub fn parse_server(line: &str) -> Option<Event> {
+    static REGEXES: OnceLock<Vec<Regex>> = OnceLock::new();
+    let regexes = REGEXES.get_or_init(|| {
+        SERVER_PATTERNS
+            .iter()
+            .map(|(pattern, _)| Regex::new(pattern).unwrap())
+            .collect()
+    });
+
+    regexes
+        .iter()
+        .zip(SERVER_PATTERNS)
+        .find_map(|(regex, (_, event))| {
+            regex
+                .captures(line)
+                .map(|captures| event(captures["player"].to_string()))
+        })
+}
+
+/// The state of a server, as seen by lazymc
+#[derive(Display, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum State {
+    #[default]
+    Sleeping,
+    Starting,
+    Online,
+    Stopping,
+}
+
+impl State {
+    /// The state after an event, events which do not change the state of the server leave it as is
+    fn next(self, event: &Event) -> State {
+        match event {
+            Event::Starting { .. } => State::Starting,
+            Event::Online => State::Online,
+            Event::Stopping | Event::StartTimedOut => State::Stopping,
+            Event::Sleeping => State::Sleeping,
+            _ => self,
+        }
+    }
+
+    /// Whether lazymc is expected to go from this state to another
+    fn expects(self, next: State) -> bool {
+        matches!(
+            (self, next),
+            (State::Sleeping, State::Starting)
+                | (State::Starting, State::Online)
+                | (State::Starting, State::Stopping)
+                | (State::Starting, State::Sleeping)
+                | (State::Online, State::Stopping)
+                | (State::Online, State::Sleeping)
+                | (State::Stopping, State::Sleeping)
+        )
+    }
+}
+
+/// The state of the server of a group and the players on it
+#[derive(Clone, Debug, Default)]
+pub struct Server {
+    pub state: State,
+    pub players: BTreeSet<String>,
+    /// When the server last started, in seconds since the unix epoch, unknown for a server which was already online
+    pub started: Option<u64>,
+}
+
+impl Server {
+    /// Apply an event, returning the previous state if the state changed
+    fn apply(&mut self, event: &Event) -> Option<State> {
+        match event {
+            Event::PlayerJoined(player) => {
+                self.players.insert(player.clone());
+            }
+            Event::PlayerLeft(player) => {
+                self.players.remove(player);
+            }
+            _ => {}
+        }
+
+        let next: State = self.state.next(event);
+        if next == self.state {
+            return None;
+        }
+        if !self.state.expects(next) {
+            // lazymc knows best, so follow it even if a message was missed
+            debug!(target: "lazymc-docker-proxy::events", "Unexpected server state transition from {} to {}", self.state, next);
+        }
+        if next != State::Online {
+            self.players.clear();
+        }
+        match next {
+            State::Starting => self.started = Some(now()),
+            State::Online => {}
+            _ => self.started = None,
+        }
+        Some(std::mem::replace(&mut self.state, next))
+    }
+}
+
+/// The server of each group, keyed by group name
+static SERVERS: Mutex<BTreeMap<String, Server>> = Mutex::new(BTreeMap::new());
+
+/// Feed an event to the state machine of a group, returning the server after the event and its previous
+/// state if the state changed
+pub fn apply(group: &str, event: &Event) -> (Server, Option<State>) {
+    let mut servers = SERVERS.lock().unwrap_or_else(|err| err.into_inner());
+    let server: &mut Server = servers.entry(group.to_string()).or_default();
+    let previous: Option<State> = server.apply(event);
+    (server.clone(), previous)
+}
+
+/// Forget the server of a group which is no longer proxied
+pub fn forget(group: &str) {
+    let mut servers = SERVERS.lock().unwrap_or_else(|err| err.into_inner());
+    servers.remove(group);
+}
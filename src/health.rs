@@ -1,29 +1,247 @@
-use std::{fs, process::exit};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    process::exit,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
 use strum::{Display, EnumString};
 
+use crate::{events::State, http};
+
+/// The file the health document is stored in
+const HEALTH_FILE: &str = "/app/health";
+
+/// Serialises updates to the health document, which is only written by the proxy process
+static LOCK: Mutex<()> = Mutex::new(());
+
 /// The status of the health check
 
-#[derive(EnumString, Display)]
+#[derive(EnumString, Display, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
 #[allow(clippy::upper_case_acronyms)]
 pub enum Status {
     STARTING,
     HEALTHY,
+    DEGRADED,
     UNHEALTHY,
 }
 
+/// The health of a single lazymc group
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GroupHealth {
+    pub status: Status,
+    /// The lazymc process for the group is running
+    pub process_running: bool,
+    /// The lazymc configuration for the group is valid
+    pub config_valid: bool,
+    /// A container was found for the group
+    pub container_found: bool,
+    /// The last attempt to start the group's containers succeeded
+    pub last_start_succeeded: bool,
+    /// The lazymc process has failed repeatedly
+    pub degraded: bool,
+    /// Number of times the lazymc process has exited unexpectedly
+    pub crashes: u32,
+    /// The reason the lazymc process last exited
+    pub last_exit: Option<String>,
+    /// The port on the proxy the group is accessible from
+    pub port: Option<u16>,
+    /// When the status of the group last changed, in seconds since the unix epoch
+    pub last_transition: u64,
+    /// The state of the server, as seen by lazymc
+    #[serde(default)]
+    pub server: State,
+    /// The players on the server
+    #[serde(default)]
+    pub players: BTreeSet<String>,
+}
+
+impl Default for GroupHealth {
+    fn default() -> Self {
+        GroupHealth {
+            status: Status::STARTING,
+            process_running: false,
+            config_valid: true,
+            container_found: true,
+            last_start_succeeded: true,
+            degraded: false,
+            crashes: 0,
+            last_exit: None,
+            port: None,
+            last_transition: now(),
+            server: State::default(),
+            players: BTreeSet::new(),
+        }
+    }
+}
+
+impl GroupHealth {
+    /// Derive the status of the group from its checks
+    fn derive_status(&self) -> Status {
+        if !self.config_valid
+            || !self.container_found
+            || !self.last_start_succeeded
+            || !self.process_running
+        {
+            Status::UNHEALTHY
+        } else if self.degraded {
+            Status::DEGRADED
+        } else {
+            Status::HEALTHY
+        }
+    }
+}
+
+/// The health document of the application and each of its groups
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Health {
+    pub status: Status,
+    /// Why the API of the backend can not be reached, if it can not
+    #[serde(default)]
+    pub backend_error: Option<String>,
+    #[serde(default)]
+    pub groups: BTreeMap<String, GroupHealth>,
+}
+
+impl Health {
+    /// The status of the application itself, which is unhealthy while the API of the backend can not be reached
+    pub fn application_status(&self) -> Status {
+        match self.backend_error {
+            Some(_) => Status::UNHEALTHY,
+            None => self.status,
+        }
+    }
+
+    /// Aggregate the status of the application and all of its groups
+    pub fn status(&self) -> Status {
+        if self.application_status() != Status::HEALTHY {
+            return self.application_status();
+        }
+
+        match self
+            .groups
+            .values()
+            .all(|group| group.status == Status::HEALTHY)
+        {
+            true => Status::HEALTHY,
+            false => Status::DEGRADED,
+        }
+    }
+}
+
+/// The current time in seconds since the unix epoch
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// Read the health document
+pub fn read() -> Health {
+    let content: String = fs::read_to_string(HEALTH_FILE).unwrap_or_default();
+
+    // the initial health file only contains the status
+    toml::from_str(&content).unwrap_or_else(|_| Health {
+        status: content.trim().parse().unwrap_or(Status::UNHEALTHY),
+        backend_error: None,
+        groups: BTreeMap::new(),
+    })
+}
+
+/// Write the health document, through a temporary file of this process so it is replaced at once
+///
+/// Only the proxy process writes the health document, the start command reports to it through its log lines.
+fn write(health: &Health) {
+    let temp_file: String = format!("{}.{}.tmp", HEALTH_FILE, std::process::id());
+    let result = toml::to_string(health)
+        .map_err(|err| err.to_string())
+        .and_then(|content| fs::write(&temp_file, content).map_err(|err| err.to_string()))
+        .and_then(|_| fs::rename(&temp_file, HEALTH_FILE).map_err(|err| err.to_string()));
+    if let Err(err) = result {
+        error!(target: "lazymc-docker-proxy::health", "Failed to write the health file {}: {}", HEALTH_FILE, err);
+        let _ = fs::remove_file(&temp_file);
+    }
+}
+
 /// Check the status
 pub fn check() -> Status {
-    let status =
-        fs::read_to_string("/app/health").unwrap_or_else(|_| Status::UNHEALTHY.to_string());
+    let status: Status = read().status();
     debug!(target: "lazymc-docker-proxy::health", "Health status: {}", status);
-    status.trim().parse().unwrap_or(Status::UNHEALTHY)
+    status
+}
+
+/// Check the status of a single group
+pub fn check_group(group: &str) -> Status {
+    let status: Status = read()
+        .groups
+        .get(group)
+        .map(|group| group.status)
+        .unwrap_or(Status::UNHEALTHY);
+    debug!(target: "lazymc-docker-proxy::health", group = group; "Health status of group {}: {}", group, status);
+    status
 }
 
 /// Set the status
 fn set(status: Status) {
     let status_str = status.to_string();
     debug!(target: "lazymc-docker-proxy::health", "Setting health status to: {}", status_str);
-    fs::write("/app/health", status_str).unwrap();
+    let _lock = LOCK.lock().unwrap_or_else(|err| err.into_inner());
+    let mut health: Health = read();
+    health.status = status;
+    write(&health);
+}
+
+/// Record whether the API of the backend can be reached, with the error if it can not
+pub fn set_backend_error(error: Option<String>) {
+    let _lock = LOCK.lock().unwrap_or_else(|err| err.into_inner());
+    let mut health: Health = read();
+    if health.backend_error == error {
+        return;
+    }
+
+    match &error {
+        Some(error) => {
+            error!(target: "lazymc-docker-proxy::health", "Application is unhealthy, the backend can not be reached: {}", error)
+        }
+        None => info!(target: "lazymc-docker-proxy::health", "The backend can be reached again."),
+    }
+    health.backend_error = error;
+    write(&health);
+}
+
+/// Update the health of a group
+pub fn update_group(group: &str, update: impl FnOnce(&mut GroupHealth)) {
+    let _lock = LOCK.lock().unwrap_or_else(|err| err.into_inner());
+    let mut health: Health = read();
+    let group_health: &mut GroupHealth = health.groups.entry(group.to_string()).or_default();
+    update(group_health);
+
+    let status: Status = group_health.derive_status();
+    if status != group_health.status {
+        debug!(target: "lazymc-docker-proxy::health", group = group; "Setting health status of group {} to: {}", group, status);
+        group_health.status = status;
+        group_health.last_transition = now();
+    }
+    write(&health);
+}
+
+/// Record whether a server was found for a group and whether starting it succeeded
+pub fn record_start(group: &str, found: bool, succeeded: bool) {
+    update_group(group, |health| {
+        health.container_found = found;
+        health.last_start_succeeded = succeeded;
+    });
+}
+
+/// Remove a group from the health document
+pub fn remove_group(group: &str) {
+    let _lock = LOCK.lock().unwrap_or_else(|err| err.into_inner());
+    let mut health: Health = read();
+    if health.groups.remove(group).is_some() {
+        write(&health);
+    }
 }
 
 pub fn healthy() {
@@ -36,10 +254,20 @@ pub fn unhealthy() {
     error!(target: "lazymc-docker-proxy::health", "Application is unhealthy.");
 }
 
-pub fn run() {
-    match check() {
-        Status::STARTING => exit(1),
-        Status::HEALTHY => exit(0),
-        Status::UNHEALTHY => exit(1),
+pub fn run(group: Option<String>) {
+    // query the http server instead of polling the health file when it is enabled
+    let status: Status = match (http::address(), &group) {
+        (Some(address), group) => http::check(&address, group.as_deref()),
+        (None, Some(group)) => check_group(group),
+        (None, None) => check(),
+    };
+
+    // a degraded app is still proxying its healthy groups, so only a check of a single group fails on it
+    match (status, group) {
+        (Status::STARTING, _) => exit(1),
+        (Status::HEALTHY, _) => exit(0),
+        (Status::DEGRADED, None) => exit(0),
+        (Status::DEGRADED, Some(_)) => exit(1),
+        (Status::UNHEALTHY, _) => exit(1),
     }
 }
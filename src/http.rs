@@ -0,0 +1,247 @@
+use serde::Serialize;
+use std::{
+    collections::BTreeSet,
+    env::var,
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    backend,
+    events::State,
+    health::{self, Health, Status},
+    metrics,
+};
+
+/// Timeout for reading a request or a health check response
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A container belonging to a group
+#[derive(Serialize)]
+struct ContainerStatus {
+    name: String,
+    state: String,
+}
+
+/// The status of a group, as returned by /status
+#[derive(Serialize)]
+struct GroupStatus {
+    group: String,
+    status: Status,
+    process: &'static str,
+    server: State,
+    players: BTreeSet<String>,
+    containers: Vec<ContainerStatus>,
+    port: Option<u16>,
+    last_transition: u64,
+    crashes: u32,
+    last_exit: Option<String>,
+}
+
+/// The status of the application, as returned by /status
+#[derive(Serialize)]
+struct ApplicationStatus {
+    status: Status,
+    backend_error: Option<String>,
+    groups: Vec<GroupStatus>,
+}
+
+/// Get the address the http server listens on, if it is enabled
+pub fn address() -> Option<String> {
+    var("LAZYMC_HTTP_ADDRESS")
+        .ok()
+        .filter(|address| !address.is_empty())
+}
+
+/// Start the http server in the background if it is enabled
+pub fn start() {
+    let Some(address) = address() else {
+        return;
+    };
+
+    let listener: TcpListener = match TcpListener::bind(&address) {
+        Ok(listener) => listener,
+        Err(err) => {
+            error!(target: "lazymc-docker-proxy::http", "Failed to listen on {}: {}", address, err);
+            health::unhealthy();
+            return;
+        }
+    };
+
+    info!(target: "lazymc-docker-proxy::http", "Listening on http://{}", address);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    thread::spawn(move || handle(stream));
+                }
+                Err(err) => {
+                    warn!(target: "lazymc-docker-proxy::http", "Failed to accept connection: {}", err);
+                }
+            }
+        }
+    });
+}
+
+/// Handle a single http request
+fn handle(mut stream: TcpStream) {
+    let _ = stream.set_read_timeout(Some(TIMEOUT));
+
+    let mut request_line: String = String::new();
+    if BufReader::new(&stream)
+        .read_line(&mut request_line)
+        .is_err()
+    {
+        return;
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = (
+        parts.next().unwrap_or_default(),
+        parts.next().unwrap_or_default(),
+    );
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let group: Option<&str> = query
+        .split('&')
+        .find_map(|param| param.strip_prefix("group="));
+    trace!(target: "lazymc-docker-proxy::http", "{} {}", method, target);
+
+    if method != "GET" {
+        respond(&mut stream, 405, "text/plain", "Method Not Allowed");
+        return;
+    }
+
+    let health: Health = health::read();
+    match path {
+        // the application is running
+        "/healthz" => match health.application_status() {
+            Status::HEALTHY => respond(&mut stream, 200, "text/plain", "OK"),
+            status => respond(&mut stream, 503, "text/plain", &status.to_string()),
+        },
+        // the application and every group, or the given group, are ready to accept players
+        "/readyz" => {
+            let status: Status = match group {
+                Some(group) => health
+                    .groups
+                    .get(group)
+                    .map(|group| group.status)
+                    .unwrap_or(Status::UNHEALTHY),
+                None => health.status(),
+            };
+            match status {
+                Status::HEALTHY => respond(&mut stream, 200, "text/plain", "OK"),
+                status => respond(&mut stream, 503, "text/plain", &status.to_string()),
+            }
+        }
+        "/status" => {
+            let status: ApplicationStatus = ApplicationStatus {
+                status: health.status(),
+                backend_error: health.backend_error,
+                groups: health
+                    .groups
+                    .into_iter()
+                    .map(|(group, group_health)| GroupStatus {
+                        containers: backend::runtime()
+                            .block_on(backend::get_states(&group))
+                            .unwrap_or_else(|err| {
+                                warn!(target: "lazymc-docker-proxy::http", "Failed to get containers for group {}: {}", group, err);
+                                Vec::new()
+                            })
+                            .into_iter()
+                            .map(|(name, state)| ContainerStatus { name, state })
+                            .collect(),
+                        group,
+                        status: group_health.status,
+                        process: match group_health.process_running {
+                            true => "running",
+                            false => "exited",
+                        },
+                        server: group_health.server,
+                        players: group_health.players,
+                        port: group_health.port,
+                        last_transition: group_health.last_transition,
+                        crashes: group_health.crashes,
+                        last_exit: group_health.last_exit,
+                    })
+                    .collect(),
+            };
+            respond(
+                &mut stream,
+                200,
+                "application/json",
+                &serde_json::to_string(&status).unwrap(),
+            );
+        }
+        "/metrics" => respond(
+            &mut stream,
+            200,
+            "text/plain; version=0.0.4",
+            &metrics::render(),
+        ),
+        _ => respond(&mut stream, 404, "text/plain", "Not Found"),
+    }
+}
+
+/// Write an http response
+fn respond(stream: &mut TcpStream, code: u16, content_type: &str, body: &str) {
+    let reason: &str = match code {
+        200 => "OK",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Service Unavailable",
+    };
+    let response: String = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        code,
+        reason,
+        content_type,
+        body.len(),
+        body
+    );
+    if let Err(err) = stream.write_all(response.as_bytes()) {
+        debug!(target: "lazymc-docker-proxy::http", "Failed to write response: {}", err);
+    }
+}
+
+/// Check the readiness of the application, or a single group, using the http server
+pub fn check(address: &str, group: Option<&str>) -> Status {
+    // the server may listen on all interfaces, but is always reachable locally
+    let address: String = match address.rsplit_once(':') {
+        Some(("0.0.0.0", port)) | Some(("[::]", port)) => format!("127.0.0.1:{}", port),
+        _ => address.to_string(),
+    };
+    let path: String = match group {
+        Some(group) => format!("/readyz?group={}", group),
+        None => "/readyz".to_string(),
+    };
+
+    let response: std::io::Result<String> = TcpStream::connect(&address).and_then(|mut stream| {
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        write!(
+            stream,
+            "GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
+            path, address
+        )?;
+        let mut response: String = String::new();
+        stream.read_to_string(&mut response)?;
+        Ok(response)
+    });
+
+    match response {
+        Ok(response) if response.starts_with("HTTP/1.1 200") => Status::HEALTHY,
+        Ok(response) => {
+            debug!(target: "lazymc-docker-proxy::health", "Health check failed: {}", response.lines().next().unwrap_or_default());
+            // the body of a failed check is the status
+            response
+                .split_once("\r\n\r\n")
+                .and_then(|(_, body)| body.parse().ok())
+                .unwrap_or(Status::UNHEALTHY)
+        }
+        Err(err) => {
+            debug!(target: "lazymc-docker-proxy::health", "Health check failed: {}", err);
+            Status::UNHEALTHY
+        }
+    }
+}
@@ -0,0 +1,565 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env::var;
+use std::fs;
+use std::path::Path;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use futures::future;
+use rustls_pki_types::{pem::PemObject, CertificateDer};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::backend::{
+    compose_scoped, disabled, first_error, proxy_id, server_labels, stages, start_order,
+    ContainerEvent, ContainerLabels, Error,
+};
+use crate::metrics;
+
+/// Directory the service account token, certificate authority and namespace of the pod are mounted in
+const SERVICE_ACCOUNT: &str = "/var/run/secrets/kubernetes.io/serviceaccount";
+
+/// Timeout for a request to the kubernetes API
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long to wait for a workload to become ready or scale down before giving up
+const READY_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// How often the state of a workload is checked while waiting for it
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often workloads are listed to find changes, as annotations can not be watched with a selector
+const WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A connection to the kubernetes API
+struct Api {
+    url: String,
+    namespace: String,
+    agent: ureq::Agent,
+}
+
+/// The kubernetes API shared by every request
+static API: OnceLock<Api> = OnceLock::new();
+
+/// Get the kubernetes API, configured from the service account of the pod on first use
+///
+/// `LAZYMC_KUBERNETES_API` overrides the address of the API server, such as `http://127.0.0.1:8001` for
+/// `kubectl proxy`, and `LAZYMC_KUBERNETES_NAMESPACE` the namespace of the pod.
+fn api() -> Result<&'static Api, Error> {
+    if let Some(api) = API.get() {
+        return Ok(api);
+    }
+
+    let url: String = match var("LAZYMC_KUBERNETES_API") {
+        Ok(url) if !url.is_empty() => url.trim_end_matches('/').to_string(),
+        _ => match (
+            var("KUBERNETES_SERVICE_HOST"),
+            var("KUBERNETES_SERVICE_PORT"),
+        ) {
+            (Ok(host), Ok(port)) => format!("https://{}:{}", host, port),
+            _ => {
+                return Err(Error::Kubernetes(
+                    "not running in a kubernetes pod, set LAZYMC_KUBERNETES_API".to_string(),
+                ))
+            }
+        },
+    };
+    let namespace: String = var("LAZYMC_KUBERNETES_NAMESPACE")
+        .ok()
+        .filter(|namespace| !namespace.is_empty())
+        .or_else(|| fs::read_to_string(format!("{}/namespace", SERVICE_ACCOUNT)).ok())
+        .map_or("default".to_string(), |namespace| {
+            namespace.trim().to_string()
+        });
+
+    let mut agent = ureq::AgentBuilder::new().timeout(TIMEOUT);
+    let ca_file: String = format!("{}/ca.crt", SERVICE_ACCOUNT);
+    if Path::new(&ca_file).exists() {
+        agent = agent.tls_config(tls_config(&ca_file)?);
+    }
+
+    // workloads belong to no Compose project, so they can only be split between proxies by their "lazymc.proxy"
+    // annotation
+    if compose_scoped() {
+        warn!(target: "lazymc-docker-proxy::kubernetes", "LAZYMC_PROXY_SCOPE=compose is ignored on kubernetes, set LAZYMC_PROXY_ID and the lazymc.proxy annotation instead, managing workloads in namespace {}", namespace);
+    }
+
+    debug!(target: "lazymc-docker-proxy::kubernetes", "Using kubernetes API at {} in namespace {}", url, namespace);
+    Ok(API.get_or_init(|| Api {
+        url,
+        namespace,
+        agent: agent.build(),
+    }))
+}
+
+/// Trust the certificate authority of the cluster
+fn tls_config(ca_file: &str) -> Result<Arc<rustls::ClientConfig>, Error> {
+    let invalid =
+        |err: String| Error::Kubernetes(format!("invalid certificate {}: {}", ca_file, err));
+
+    let mut roots = rustls::RootCertStore::empty();
+    for certificate in
+        CertificateDer::pem_file_iter(ca_file).map_err(|err| invalid(err.to_string()))?
+    {
+        roots
+            .add(certificate.map_err(|err| invalid(err.to_string()))?)
+            .map_err(|err| invalid(err.to_string()))?;
+    }
+
+    let config = rustls::ClientConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .map_err(|err| invalid(err.to_string()))?
+    .with_root_certificates(roots)
+    .with_no_client_auth();
+    Ok(Arc::new(config))
+}
+
+/// Make a request to the kubernetes API, sending a JSON merge patch if given
+async fn request<T: DeserializeOwned + Send + 'static>(
+    path: String,
+    patch: Option<serde_json::Value>,
+    group: &str,
+) -> Result<T, Error> {
+    let api: &'static Api = api()?;
+
+    // ureq blocks, so requests are made off the runtime
+    let response = tokio::task::spawn_blocking(move || {
+        let url: String = format!("{}{}", api.url, path);
+        let mut request = match patch {
+            Some(_) => api
+                .agent
+                .patch(&url)
+                .set("Content-Type", "application/merge-patch+json"),
+            None => api.agent.get(&url),
+        };
+        // the token is rotated by kubernetes, so it is read for every request
+        if let Ok(token) = fs::read_to_string(format!("{}/token", SERVICE_ACCOUNT)) {
+            request = request.set("Authorization", &format!("Bearer {}", token.trim()));
+        }
+
+        let response = match patch {
+            Some(patch) => request.send_string(&patch.to_string()),
+            None => request.call(),
+        };
+        match response {
+            Ok(response) => response
+                .into_string()
+                .map_err(|err| err.to_string())
+                .and_then(|body| serde_json::from_str::<T>(&body).map_err(|err| err.to_string())),
+            // the API explains what went wrong in the body of the response
+            Err(ureq::Error::Status(code, response)) => Err(format!(
+                "{} returned {}: {}",
+                path,
+                code,
+                response.into_string().unwrap_or_default()
+            )),
+            Err(err) => Err(err.to_string()),
+        }
+    })
+    .await
+    .map_err(|err| err.to_string())
+    .and_then(|response| response);
+
+    response.map_err(|err| {
+        metrics::report_docker_error(group, &err);
+        Error::Kubernetes(err)
+    })
+}
+
+/// A list of kubernetes objects
+#[derive(Deserialize)]
+struct List<T> {
+    items: Vec<T>,
+}
+
+#[derive(Deserialize, Clone, Default)]
+struct Metadata {
+    name: String,
+    #[serde(default)]
+    annotations: HashMap<String, String>,
+}
+
+#[derive(Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+struct Selector {
+    #[serde(default)]
+    match_labels: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize, Clone, Default)]
+struct WorkloadSpec {
+    replicas: Option<i64>,
+    #[serde(default)]
+    selector: Selector,
+}
+
+#[derive(Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+struct WorkloadStatus {
+    #[serde(default)]
+    ready_replicas: i64,
+}
+
+/// A deployment or statefulset
+#[derive(Deserialize, Clone)]
+struct Workload {
+    #[serde(skip)]
+    kind: &'static str,
+    metadata: Metadata,
+    #[serde(default)]
+    spec: WorkloadSpec,
+    #[serde(default)]
+    status: WorkloadStatus,
+}
+
+#[derive(Deserialize, Default)]
+struct PodStatus {
+    phase: Option<String>,
+    #[serde(rename = "podIP")]
+    pod_ip: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Pod {
+    #[serde(default)]
+    status: PodStatus,
+}
+
+/// The kinds of workload which are scaled, by the path of their API
+const KINDS: &[&str] = &["deployments", "statefulsets"];
+
+impl Workload {
+    /// The kind and name of the workload, such as `deployment/mc`
+    fn name(&self) -> String {
+        format!("{}/{}", self.kind.trim_end_matches('s'), self.metadata.name)
+    }
+
+    /// The API path of the workload
+    fn path(&self) -> String {
+        format!(
+            "/apis/apps/v1/namespaces/{}/{}/{}",
+            api().map_or("default", |api| api.namespace.as_str()),
+            self.kind,
+            self.metadata.name
+        )
+    }
+
+    fn start_order(&self) -> i32 {
+        start_order(Some(&self.metadata.annotations), &self.name())
+    }
+}
+
+/// List the deployments and statefulsets whose annotation matches the given value, or which have the annotation
+/// at all if no value is given
+///
+/// Only those whose "lazymc.proxy" annotation matches `LAZYMC_PROXY_ID` are listed when it is set.
+async fn list(annotation: &str, value: Option<&str>, group: &str) -> Result<Vec<Workload>, Error> {
+    let namespace: &str = &api()?.namespace;
+    let mut workloads: Vec<Workload> = Vec::new();
+    for &kind in KINDS {
+        let list: List<Workload> = request(
+            format!("/apis/apps/v1/namespaces/{}/{}", namespace, kind),
+            None,
+            group,
+        )
+        .await?;
+        workloads.extend(
+            list.items
+                .into_iter()
+                .filter(
+                    |workload| match workload.metadata.annotations.get(annotation) {
+                        Some(annotated) => value.map_or(true, |value| annotated == value),
+                        None => false,
+                    },
+                )
+                .filter(|workload| {
+                    proxy_id().map_or(true, |id| {
+                        workload
+                            .metadata
+                            .annotations
+                            .get("lazymc.proxy")
+                            .map(String::as_str)
+                            == Some(id)
+                    })
+                })
+                .map(|workload| Workload { kind, ..workload }),
+        );
+    }
+    Ok(workloads)
+}
+
+/// List the pods of a workload
+async fn pods(workload: &Workload, group: &str) -> Result<Vec<Pod>, Error> {
+    let selector: Vec<String> = workload
+        .spec
+        .selector
+        .match_labels
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect();
+    let list: List<Pod> = request(
+        format!(
+            "/api/v1/namespaces/{}/pods?labelSelector={}",
+            api()?.namespace,
+            selector.join(",")
+        ),
+        None,
+        group,
+    )
+    .await?;
+    Ok(list.items)
+}
+
+/// Get the IP address of the running pod of a workload, or the last one it had while it has no running pod
+///
+/// The address is not given up when the workload is scaled to 0, otherwise lazymc would be restarted with the address
+/// of its service each time the server sleeps, which wakes it again.
+async fn ip_address(workload: &Workload) -> Option<String> {
+    static POD_IPS: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+
+    let pod_ip: Option<String> = pods(workload, "")
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|pod| pod.status.phase.as_deref() == Some("Running"))
+        .find_map(|pod| pod.status.pod_ip)
+        .filter(|pod_ip| !pod_ip.is_empty());
+
+    let mut pod_ips = POD_IPS.lock().unwrap_or_else(|err| err.into_inner());
+    match pod_ip {
+        Some(pod_ip) => {
+            pod_ips.insert(workload.name(), pod_ip.clone());
+            Some(pod_ip)
+        }
+        None => pod_ips.get(&workload.name()).cloned(),
+    }
+}
+
+/// Scale a workload to the given number of replicas
+async fn scale(workload: &Workload, replicas: i64, group: &str) -> Result<(), Error> {
+    if workload.spec.replicas == Some(replicas) {
+        return Ok(());
+    }
+
+    info!(target: "lazymc-docker-proxy::kubernetes", "Scaling {} to {} replica(s)", workload.name(), replicas);
+    request::<serde_json::Value>(
+        format!("{}/scale", workload.path()),
+        Some(json!({ "spec": { "replicas": replicas } })),
+        group,
+    )
+    .await
+    .map(|_| ())
+    .map_err(|err| {
+        error!(target: "lazymc-docker-proxy::kubernetes", "Error scaling {}: {}", workload.name(), err);
+        err
+    })
+}
+
+/// Wait until a workload has a ready replica
+async fn wait_until_ready(workload: &Workload, group: &str) -> Result<(), Error> {
+    debug!(target: "lazymc-docker-proxy::kubernetes", "Waiting for {} to be ready...", workload.name());
+    let deadline: Instant = Instant::now() + READY_TIMEOUT;
+    loop {
+        let current: Workload = request(workload.path(), None, group).await?;
+        if current.status.ready_replicas > 0 {
+            info!(target: "lazymc-docker-proxy::kubernetes", "{} is ready", workload.name());
+            return Ok(());
+        }
+
+        if Instant::now() >= deadline {
+            return Err(Error::NotReady {
+                container: workload.name(),
+                reason: format!("was not ready after {} seconds", READY_TIMEOUT.as_secs()),
+            });
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// Wait until a workload has no pods left
+async fn wait_until_stopped(workload: &Workload, group: &str) -> Result<(), Error> {
+    let deadline: Instant = Instant::now() + READY_TIMEOUT;
+    loop {
+        if pods(workload, group).await?.is_empty() {
+            info!(target: "lazymc-docker-proxy::kubernetes", "{} stopped", workload.name());
+            return Ok(());
+        }
+
+        if Instant::now() >= deadline {
+            return Err(Error::NotReady {
+                container: workload.name(),
+                reason: format!(
+                    "was still running after {} seconds",
+                    READY_TIMEOUT.as_secs()
+                ),
+            });
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// Scale the given workloads to 0 replicas in the reverse of their start order, the workloads of each stage concurrently
+async fn stop_workloads(workloads: Vec<Workload>, group: &str) -> Result<(), Error> {
+    let mut result: Result<(), Error> = Ok(());
+    for stage in stages(workloads, Workload::start_order).into_iter().rev() {
+        let results: Vec<Result<(), Error>> =
+            future::join_all(stage.iter().map(|workload| async move {
+                scale(workload, 0, group).await?;
+                wait_until_stopped(workload, group).await
+            }))
+            .await;
+
+        // keep stopping the remaining stages, a workload which failed to stop should not keep the others running
+        result = result.and(first_error(results));
+    }
+    result
+}
+
+/// Check that the kubernetes API can be reached
+pub async fn ping() -> Result<(), Error> {
+    request::<serde_json::Value>("/version".to_string(), None, "")
+        .await
+        .map(|_| ())
+}
+
+/// Scale the workloads with the annotation "lazymc.group=group" to 0 replicas
+pub async fn stop(group: &str) -> Result<(), Error> {
+    let workloads: Vec<Workload> = list("lazymc.group", Some(group), group).await?;
+    debug!(target: "lazymc-docker-proxy::kubernetes", "Found {} workload(s) to stop", workloads.len());
+    stop_workloads(workloads, group).await
+}
+
+/// Scale the workloads of a group to 1 replica in stages ordered by their "lazymc.start_order" annotation, waiting
+/// for each workload to be ready, returning whether any workload was found
+pub async fn start(group: &str) -> Result<bool, Error> {
+    let workloads: Vec<Workload> = list("lazymc.group", Some(group), group).await?;
+    let found: bool = !workloads.is_empty();
+    let stages: Vec<Vec<Workload>> = stages(workloads, Workload::start_order);
+    let stage_count: usize = stages.len();
+
+    for (index, stage) in stages.into_iter().enumerate() {
+        debug!(target: "lazymc-docker-proxy::kubernetes", "Found {} workload(s) to start in stage {} of {}", stage.len(), index + 1, stage_count);
+        first_error(
+            future::join_all(stage.iter().map(|workload| async move {
+                scale(workload, 1, group).await?;
+                wait_until_ready(workload, group).await
+            }))
+            .await,
+        )?;
+    }
+
+    Ok(found)
+}
+
+/// Wait until every workload with the annotation "lazymc.group=group" is ready
+pub async fn wait_for_group(group: &str) -> Result<(), Error> {
+    let workloads: Vec<Workload> = list("lazymc.group", Some(group), group).await?;
+    first_error(
+        future::join_all(
+            workloads
+                .iter()
+                .map(|workload| wait_until_ready(workload, group)),
+        )
+        .await,
+    )
+}
+
+/// Scale all workloads with the annotation "lazymc.group" to 0 replicas except those of the given groups and those with
+/// the annotation "lazymc.enabled=false", each group in the reverse of its start order
+pub async fn stop_all(except: &HashSet<String>) -> Result<(), Error> {
+    let workloads: Vec<Workload> = list("lazymc.group", None, "")
+        .await?
+        .into_iter()
+        .filter(|workload| !disabled(Some(&workload.metadata.annotations)))
+        .collect();
+    debug!(target: "lazymc-docker-proxy::kubernetes", "Found {} workload(s) to stop", workloads.len());
+
+    let mut groups: HashMap<String, Vec<Workload>> = HashMap::new();
+    for workload in workloads {
+        let group: String = workload.metadata.annotations["lazymc.group"].clone();
+        if !except.contains(&group) {
+            groups.entry(group).or_default().push(workload);
+        }
+    }
+
+    first_error(
+        future::join_all(
+            groups
+                .into_iter()
+                .map(|(group, workloads)| async move { stop_workloads(workloads, &group).await }),
+        )
+        .await,
+    )
+}
+
+/// Get the name of each workload with the annotation "lazymc.group=group" and whether it is running
+pub async fn get_states(group: &str) -> Result<Vec<(String, String)>, Error> {
+    let workloads: Vec<Workload> = list("lazymc.group", Some(group), group).await?;
+    Ok(workloads
+        .iter()
+        .map(|workload| {
+            let state: &str = match (workload.spec.replicas, workload.status.ready_replicas) {
+                (_, ready) if ready > 0 => "running",
+                (Some(replicas), _) if replicas > 0 => "starting",
+                _ => "stopped",
+            };
+            (workload.name(), state.to_string())
+        })
+        .collect())
+}
+
+/// Get all annotations for workloads with the annotation "lazymc.enabled=true"
+pub async fn get_labels() -> Result<Vec<ContainerLabels>, Error> {
+    let workloads: Vec<Workload> = list("lazymc.enabled", Some("true"), "").await?;
+    debug!(target: "lazymc-docker-proxy::kubernetes", "Found {} workload(s) to get annotations", workloads.len());
+
+    let mut label_sets: Vec<ContainerLabels> = Vec::new();
+    for workload in workloads {
+        let ip_address: Option<String> = ip_address(&workload).await;
+        label_sets.push(server_labels(
+            workload.name(),
+            workload.metadata.annotations,
+            ip_address,
+        ));
+    }
+    Ok(label_sets)
+}
+
+/// The annotations and pod IP of each workload, keyed by name
+type Snapshot = BTreeMap<String, (HashMap<String, String>, Option<String>)>;
+
+/// Watch for workloads with the annotation "lazymc.enabled=true" being created, removed, annotated or moving to a
+/// new pod IP, by comparing their annotations and addresses every few seconds
+///
+/// Returns when listing the workloads fails or the receiver is dropped.
+pub async fn watch(sender: Sender<ContainerEvent>) -> Result<(), Error> {
+    let mut previous: Option<Snapshot> = None;
+
+    debug!(target: "lazymc-docker-proxy::kubernetes", "Watching for workload changes...");
+    loop {
+        let mut current: Snapshot = BTreeMap::new();
+        for workload in list("lazymc.enabled", Some("true"), "").await? {
+            let ip_address: Option<String> = ip_address(&workload).await;
+            current.insert(workload.name(), (workload.metadata.annotations, ip_address));
+        }
+
+        if previous
+            .as_ref()
+            .is_some_and(|previous| *previous != current)
+        {
+            let event = ContainerEvent {
+                action: "update".to_string(),
+                group: None,
+            };
+            if sender.send(event).is_err() {
+                return Ok(());
+            }
+        }
+        previous = Some(current);
+        tokio::time::sleep(WATCH_INTERVAL).await;
+    }
+}
@@ -1,7 +1,13 @@
 use std::env;
+use std::io::Write;
 
 extern crate pretty_env_logger;
 
+/// Whether logs are written as JSON lines, enabled by setting LAZYMC_LOG_FORMAT to "json"
+pub fn json() -> bool {
+    env::var("LAZYMC_LOG_FORMAT").is_ok_and(|format| format.eq_ignore_ascii_case("json"))
+}
+
 /// Initialize the logger
 pub fn init() {
     // Set default log level if none is set
@@ -9,5 +15,32 @@ pub fn init() {
         env::set_var("RUST_LOG", "info");
     }
 
-    pretty_env_logger::init();
+    if !json() {
+        pretty_env_logger::init();
+        return;
+    }
+
+    let mut builder = pretty_env_logger::formatted_builder();
+    builder.parse_filters(&env::var("RUST_LOG").unwrap_or_default());
+    builder.format(|buf, record| {
+        // lazymc output is logged with the group as a prefix of its target, which is split into its own field
+        let group: Option<String> = record
+            .key_values()
+            .get("group".into())
+            .map(|group| group.to_string());
+        let target: &str = group
+            .as_ref()
+            .and_then(|group| record.target().strip_prefix(&format!("{}::", group)))
+            .unwrap_or(record.target());
+
+        let line = serde_json::json!({
+            "timestamp": buf.timestamp_millis().to_string(),
+            "level": record.level().to_string(),
+            "target": target,
+            "group": group,
+            "message": record.args().to_string(),
+        });
+        writeln!(buf, "{}", line)
+    });
+    builder.init();
 }
@@ -1,13 +1,22 @@
 #[macro_use]
 extern crate log;
 
+mod backend;
 mod command;
 mod docker;
 mod entrypoint;
+mod events;
 mod health;
+mod http;
+mod kubernetes;
 mod logging;
+mod metrics;
+mod notify;
+mod rcon;
+mod render;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
 
 /// Wrapper for lazymc to run against a docker minecraft server
 #[derive(Parser, Debug)]
@@ -17,13 +26,38 @@ struct Args {
     #[arg(short, long)]
     command: bool,
 
-    /// The lazymc group name
+    /// The lazymc group name, also used with --health to check a single group
     #[arg(short, long, requires_if("command", "true"))]
     group: Option<String>,
 
+    /// Wait for the server containers to be healthy when running as a lazymc start command
+    #[arg(long, requires = "command")]
+    wait_for_health: bool,
+
+    /// Pause the server containers while lazymc freezes the server when running as a lazymc start command
+    #[arg(long, requires = "command")]
+    freeze: bool,
+
+    /// Pause the server containers while the start command with this pid is frozen by lazymc
+    #[arg(long, hide = true, requires = "group")]
+    watch_freeze: Option<u32>,
+
     /// Execute with this flag when running as a health check
     #[arg(short, long)]
     health: bool,
+
+    #[command(subcommand)]
+    subcommand: Option<Commands>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Print the generated lazymc config of each group without starting or stopping any containers
+    Render {
+        /// Write the config of each group to this directory instead of printing it
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
 }
 
 /// Main entrypoint for the application
@@ -32,10 +66,14 @@ fn main() {
 
     let args: Args = Args::parse();
 
-    if args.command {
-        command::run(args.group.unwrap());
+    if let Some(Commands::Render { output }) = args.subcommand {
+        render::run(output);
+    } else if let Some(pid) = args.watch_freeze {
+        command::watch_freeze(args.group.unwrap(), pid);
+    } else if args.command {
+        command::run(args.group.unwrap(), args.wait_for_health, args.freeze);
     } else if args.health {
-        health::run();
+        health::run(args.group);
     } else {
         entrypoint::run();
     }
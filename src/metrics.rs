@@ -0,0 +1,197 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::Instant,
+};
+
+/// Metrics for each group, keyed by group name
+static METRICS: Mutex<BTreeMap<String, GroupMetrics>> = Mutex::new(BTreeMap::new());
+
+/// Whether this process reports its metrics to the proxy through its log lines, as the start command does, instead
+/// of counting them itself
+static REPORTED: AtomicBool = AtomicBool::new(false);
+
+/// Metrics for a single group
+#[derive(Default)]
+struct GroupMetrics {
+    wakes: u64,
+    sleeps: u64,
+    forced_stops: u64,
+    kicks: u64,
+    restarts: u64,
+    docker_errors: u64,
+    awake: bool,
+    starting_since: Option<Instant>,
+    start_duration_sum: f64,
+    start_duration_count: u64,
+}
+
+/// Update the metrics of a group
+fn update(group: &str, update: impl FnOnce(&mut GroupMetrics)) {
+    let mut metrics = METRICS.lock().unwrap_or_else(|err| err.into_inner());
+    update(metrics.entry(group.to_string()).or_default());
+}
+
+/// Make a group known, so that its metrics are exported before anything happens to it
+pub fn register(group: &str) {
+    update(group, |_| {});
+}
+
+/// The server containers of a group are being started
+pub fn wake(group: &str) {
+    update(group, |metrics| {
+        metrics.wakes += 1;
+        metrics.starting_since = Some(Instant::now());
+    });
+}
+
+/// The server of a group is online
+pub fn online(group: &str) {
+    update(group, |metrics| {
+        metrics.awake = true;
+        if let Some(starting_since) = metrics.starting_since.take() {
+            metrics.start_duration_sum += starting_since.elapsed().as_secs_f64();
+            metrics.start_duration_count += 1;
+        }
+    });
+}
+
+/// The server of a group is sleeping
+pub fn sleep(group: &str) {
+    update(group, |metrics| {
+        metrics.sleeps += 1;
+        metrics.awake = false;
+        metrics.starting_since = None;
+    });
+}
+
+/// The server containers of a group were forcefully stopped
+pub fn forced_stop(group: &str) {
+    update(group, |metrics| metrics.forced_stops += 1);
+}
+
+/// lazymc kicked a player because the server of a group was still starting
+pub fn kick(group: &str) {
+    update(group, |metrics| metrics.kicks += 1);
+}
+
+/// The lazymc process of a group was restarted after it exited
+pub fn restart(group: &str) {
+    update(group, |metrics| metrics.restarts += 1);
+}
+
+/// A docker API call failed, the group is empty if the call was not made for a single group
+pub fn docker_error(group: &str) {
+    update(group, |metrics| metrics.docker_errors += 1);
+}
+
+/// Report metrics to the proxy through log lines from now on, for processes started by lazymc whose output the
+/// proxy parses
+pub fn report_through_log() {
+    REPORTED.store(true, Ordering::Relaxed);
+}
+
+/// Count a failed docker API call, or report it to the proxy, which counts it once it parses the log line
+pub fn report_docker_error(group: &str, err: &dyn fmt::Display) {
+    match REPORTED.load(Ordering::Relaxed) {
+        true => {
+            warn!(target: "lazymc-docker-proxy::metrics", group = group; "Docker API call failed for group {}: {}", group, err)
+        }
+        false => docker_error(group),
+    }
+}
+
+/// Escape a label value for the prometheus text exposition format
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// A sample of a metric family, the suffix of its name and how its value is read from the metrics of a group
+type Sample<'a> = (&'a str, &'a dyn Fn(&GroupMetrics) -> String);
+
+/// Render the metrics in the prometheus text exposition format
+pub fn render() -> String {
+    let metrics = METRICS.lock().unwrap_or_else(|err| err.into_inner());
+    let mut output: String = String::new();
+
+    let mut family = |name: &str, kind: &str, help: &str, samples: &[Sample]| {
+        writeln!(output, "# HELP {} {}", name, help).unwrap();
+        writeln!(output, "# TYPE {} {}", name, kind).unwrap();
+        for (group, group_metrics) in metrics.iter() {
+            for (suffix, value) in samples {
+                writeln!(
+                    output,
+                    "{}{}{{group=\"{}\"}} {}",
+                    name,
+                    suffix,
+                    escape(group),
+                    value(group_metrics)
+                )
+                .unwrap();
+            }
+        }
+    };
+
+    family(
+        "lazymc_wakes_total",
+        "counter",
+        "Number of times the server containers were started.",
+        &[("", &|metrics| metrics.wakes.to_string())],
+    );
+    family(
+        "lazymc_sleeps_total",
+        "counter",
+        "Number of times the server went to sleep.",
+        &[("", &|metrics| metrics.sleeps.to_string())],
+    );
+    family(
+        "lazymc_forced_stops_total",
+        "counter",
+        "Number of times the server containers were forcefully stopped after lazymc failed to stop them.",
+        &[("", &|metrics| metrics.forced_stops.to_string())],
+    );
+    family(
+        "lazymc_kicks_while_starting_total",
+        "counter",
+        "Number of players kicked because the server was still starting.",
+        &[("", &|metrics| metrics.kicks.to_string())],
+    );
+    family(
+        "lazymc_process_restarts_total",
+        "counter",
+        "Number of times the lazymc process was restarted after it exited.",
+        &[("", &|metrics| metrics.restarts.to_string())],
+    );
+    family(
+        "lazymc_docker_errors_total",
+        "counter",
+        "Number of failed docker API calls.",
+        &[("", &|metrics| metrics.docker_errors.to_string())],
+    );
+    family(
+        "lazymc_awake",
+        "gauge",
+        "Whether the server is online (1) or asleep (0).",
+        &[("", &|metrics| u8::from(metrics.awake).to_string())],
+    );
+    family(
+        "lazymc_start_duration_seconds",
+        "summary",
+        "Time taken from starting the server containers until the server was online.",
+        &[
+            ("_sum", &|metrics| metrics.start_duration_sum.to_string()),
+            ("_count", &|metrics| {
+                metrics.start_duration_count.to_string()
+            }),
+        ],
+    );
+
+    output
+}
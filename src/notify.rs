@@ -0,0 +1,268 @@
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    env::var,
+    process::Command,
+    sync::Mutex,
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use strum::{Display, EnumString};
+
+/// Timeout for delivering a notification to a webhook
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Number of times delivery of a notification is attempted
+const ATTEMPTS: u32 = 3;
+
+/// Delay before retrying a failed delivery, doubled after each attempt
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// The events notifications can be sent for
+pub const EVENTS: &[&str] = &[
+    "wake",
+    "online",
+    "sleep",
+    "crash",
+    "forced_stop",
+    "start_failed",
+];
+
+/// The notifiers of each group, keyed by group name
+static NOTIFIERS: Mutex<BTreeMap<String, Notifiers>> = Mutex::new(BTreeMap::new());
+
+/// What happened to a group
+#[derive(EnumString, Display, Clone, Copy, PartialEq, Eq, Debug)]
+#[strum(serialize_all = "snake_case")]
+pub enum Kind {
+    /// The server is being started
+    Wake,
+    /// The server is online
+    Online,
+    /// The server is sleeping
+    Sleep,
+    /// The server or its lazymc process stopped unexpectedly
+    Crash,
+    /// The server containers were forcefully stopped
+    ForcedStop,
+    /// The server failed to start
+    StartFailed,
+}
+
+/// A notification about a group
+#[derive(Serialize, Clone, Debug)]
+pub struct Notification {
+    pub group: String,
+    pub event: String,
+    pub message: String,
+    pub player: Option<String>,
+    /// When the event happened, in seconds since the unix epoch
+    pub timestamp: u64,
+}
+
+/// A generic webhook, posted a JSON body
+#[derive(Clone, Debug)]
+struct Webhook {
+    url: String,
+    /// Template of the body, the whole notification is posted if not set
+    template: Option<String>,
+}
+
+/// Where the notifications of a group are sent, configured by labels with environment variables as defaults
+#[derive(Clone, Debug, Default)]
+pub struct Notifiers {
+    webhook: Option<Webhook>,
+    discord: Option<String>,
+    script: Option<String>,
+    /// The events to notify of, all events if not set
+    events: Option<Vec<Kind>>,
+}
+
+impl Notifiers {
+    /// Create the notifiers of a group from its labels, falling back to the `LAZYMC_NOTIFY_*` environment variables
+    pub fn new(
+        webhook_url: Option<String>,
+        webhook_template: Option<String>,
+        discord_url: Option<String>,
+        script: Option<String>,
+        events: Option<Vec<String>>,
+    ) -> Self {
+        let env = |name: &str| var(name).ok().filter(|value| !value.is_empty());
+
+        Notifiers {
+            webhook: webhook_url
+                .or_else(|| env("LAZYMC_NOTIFY_WEBHOOK_URL"))
+                .map(|url| Webhook {
+                    url,
+                    template: webhook_template.or_else(|| env("LAZYMC_NOTIFY_WEBHOOK_TEMPLATE")),
+                }),
+            discord: discord_url.or_else(|| env("LAZYMC_NOTIFY_DISCORD_URL")),
+            script: script.or_else(|| env("LAZYMC_NOTIFY_SCRIPT")),
+            events: events
+                .or_else(|| {
+                    env("LAZYMC_NOTIFY_EVENTS")
+                        .map(|events| events.split(',').map(|event| event.trim().to_string()).collect())
+                })
+                .map(|events| {
+                    events
+                        .iter()
+                        .filter_map(|event| match event.parse() {
+                            Ok(kind) => Some(kind),
+                            Err(_) => {
+                                warn!(target: "lazymc-docker-proxy::notify", "Ignoring unknown notification event: {}", event);
+                                None
+                            }
+                        })
+                        .collect()
+                }),
+        }
+    }
+
+    /// Whether any notifier is configured
+    fn is_empty(&self) -> bool {
+        self.webhook.is_none() && self.discord.is_none() && self.script.is_none()
+    }
+}
+
+/// Set the notifiers of a group
+pub fn register(group: &str, notifiers: Notifiers) {
+    let mut all = NOTIFIERS.lock().unwrap_or_else(|err| err.into_inner());
+    all.insert(group.to_string(), notifiers);
+}
+
+/// Forget the notifiers of a group which is no longer proxied
+pub fn forget(group: &str) {
+    let mut all = NOTIFIERS.lock().unwrap_or_else(|err| err.into_inner());
+    all.remove(group);
+}
+
+/// Send a notification to the notifiers of a group in the background, so the caller is never held up by delivery
+pub fn send(group: &str, kind: Kind, message: String, player: Option<String>) {
+    let notifiers: Notifiers = match NOTIFIERS
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .get(group)
+    {
+        Some(notifiers) => notifiers.clone(),
+        None => return,
+    };
+    if notifiers.is_empty()
+        || notifiers
+            .events
+            .as_ref()
+            .is_some_and(|events| !events.contains(&kind))
+    {
+        return;
+    }
+
+    let notification: Notification = Notification {
+        group: group.to_string(),
+        event: kind.to_string(),
+        message,
+        player,
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default(),
+    };
+    debug!(target: "lazymc-docker-proxy::notify", group = group; "Sending {} notification for group {}", kind, group);
+
+    if let Some(webhook) = notifiers.webhook {
+        let notification = notification.clone();
+        deliver("webhook", group, move || {
+            let body: String = match &webhook.template {
+                Some(template) => render(template, &notification),
+                None => serde_json::to_string(&notification).unwrap(),
+            };
+            post(&webhook.url, &body)
+        });
+    }
+    if let Some(url) = notifiers.discord {
+        let notification = notification.clone();
+        deliver("discord", group, move || {
+            let body = serde_json::json!({ "content": format!("**{}**: {}", notification.group, notification.message) });
+            post(&url, &body.to_string())
+        });
+    }
+    if let Some(script) = notifiers.script {
+        deliver("script", group, move || run(&script, &notification));
+    }
+}
+
+/// Deliver a notification for a group on its own thread, retrying with a growing delay when it fails
+fn deliver(
+    notifier: &'static str,
+    group: &str,
+    attempt: impl Fn() -> Result<(), String> + Send + 'static,
+) {
+    let group: String = group.to_string();
+    thread::spawn(move || {
+        let mut delay: Duration = RETRY_DELAY;
+        for attempts in 1..=ATTEMPTS {
+            match attempt() {
+                Ok(()) => return,
+                Err(err) if attempts < ATTEMPTS => {
+                    warn!(target: "lazymc-docker-proxy::notify", group = group.as_str(); "Failed to send {} notification, retrying in {} seconds: {}", notifier, delay.as_secs(), err);
+                    thread::sleep(delay);
+                    delay *= 2;
+                }
+                Err(err) => {
+                    error!(target: "lazymc-docker-proxy::notify", group = group.as_str(); "Failed to send {} notification after {} attempts: {}", notifier, ATTEMPTS, err);
+                }
+            }
+        }
+    });
+}
+
+/// Fill in the `{group}`, `{event}`, `{message}`, `{player}` and `{timestamp}` placeholders of a template,
+/// escaping the values so they can be placed inside JSON strings
+fn render(template: &str, notification: &Notification) -> String {
+    let escape = |value: &str| {
+        let quoted: String = serde_json::to_string(value).unwrap();
+        quoted[1..quoted.len() - 1].to_string()
+    };
+    template
+        .replace("{group}", &escape(&notification.group))
+        .replace("{event}", &escape(&notification.event))
+        .replace("{message}", &escape(&notification.message))
+        .replace(
+            "{player}",
+            &escape(notification.player.as_deref().unwrap_or_default()),
+        )
+        .replace("{timestamp}", &notification.timestamp.to_string())
+}
+
+/// Post a JSON body to a url
+fn post(url: &str, body: &str) -> Result<(), String> {
+    ureq::AgentBuilder::new()
+        .timeout(TIMEOUT)
+        .build()
+        .post(url)
+        .set("Content-Type", "application/json")
+        .send_string(body)
+        .map(|_| ())
+        .map_err(|err| err.to_string())
+}
+
+/// Run a script with the event and group as arguments and the notification in its environment
+fn run(script: &str, notification: &Notification) -> Result<(), String> {
+    let status = Command::new(script)
+        .args([&notification.event, &notification.group])
+        .env("LAZYMC_NOTIFY_GROUP", &notification.group)
+        .env("LAZYMC_NOTIFY_EVENT", &notification.event)
+        .env("LAZYMC_NOTIFY_MESSAGE", &notification.message)
+        .env(
+            "LAZYMC_NOTIFY_PLAYER",
+            notification.player.as_deref().unwrap_or_default(),
+        )
+        .env(
+            "LAZYMC_NOTIFY_TIMESTAMP",
+            notification.timestamp.to_string(),
+        )
+        .status()
+        .map_err(|err| err.to_string())?;
+    match status.success() {
+        true => Ok(()),
+        false => Err(format!("{} exited with {}", script, status)),
+    }
+}
@@ -0,0 +1,117 @@
+use std::io::{Error, ErrorKind, Result};
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+/// Timeout for connecting, logging in and sending a request
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Timeout for the response to a command, saving a large world can take a while
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Packet type of a login request
+const LOGIN: i32 = 3;
+
+/// Packet type of a command request, and of a login response
+const COMMAND: i32 = 2;
+
+/// Largest response packet the server sends
+const MAX_PACKET_LENGTH: i32 = 4096 + 10;
+
+/// A client for the minecraft RCON protocol
+pub struct Client {
+    stream: TcpStream,
+    next_id: i32,
+}
+
+impl Client {
+    /// Connect to a server and log in with the given password
+    pub async fn connect(address: &str, password: &str) -> Result<Self> {
+        let stream: TcpStream = timeout(TIMEOUT, TcpStream::connect(address))
+            .await
+            .map_err(|_| Error::new(ErrorKind::TimedOut, "timed out connecting"))??;
+        let mut client: Client = Client { stream, next_id: 1 };
+
+        let id: i32 = client.send(LOGIN, password).await?;
+        // the server responds to a failed login with an id of -1
+        loop {
+            let (response_id, kind, _) = client.receive(TIMEOUT).await?;
+            if kind != COMMAND {
+                continue;
+            }
+            return match response_id == id {
+                true => Ok(client),
+                false => Err(Error::new(
+                    ErrorKind::PermissionDenied,
+                    "login failed, check the RCON password",
+                )),
+            };
+        }
+    }
+
+    /// Run a command and return its response
+    pub async fn command(&mut self, command: &str) -> Result<String> {
+        let id: i32 = self.send(COMMAND, command).await?;
+        loop {
+            let (response_id, _, body) = self.receive(COMMAND_TIMEOUT).await?;
+            if response_id == id {
+                return Ok(body);
+            }
+        }
+    }
+
+    /// Run a command without waiting for its response, for commands after which the server may close the connection
+    pub async fn command_no_response(&mut self, command: &str) -> Result<()> {
+        self.send(COMMAND, command).await.map(|_| ())
+    }
+
+    /// Send a packet, returning its id
+    async fn send(&mut self, kind: i32, body: &str) -> Result<i32> {
+        let id: i32 = self.next_id;
+        self.next_id += 1;
+
+        // the length covers the id, type, body and the two terminating null bytes
+        let mut packet: Vec<u8> = Vec::with_capacity(body.len() + 14);
+        packet.extend_from_slice(&(body.len() as i32 + 10).to_le_bytes());
+        packet.extend_from_slice(&id.to_le_bytes());
+        packet.extend_from_slice(&kind.to_le_bytes());
+        packet.extend_from_slice(body.as_bytes());
+        packet.extend_from_slice(&[0, 0]);
+
+        timeout(TIMEOUT, self.stream.write_all(&packet))
+            .await
+            .map_err(|_| Error::new(ErrorKind::TimedOut, "timed out sending request"))??;
+        Ok(id)
+    }
+
+    /// Receive a packet, returning its id, type and body
+    async fn receive(&mut self, wait: Duration) -> Result<(i32, i32, String)> {
+        timeout(wait, async {
+            let length: i32 = self.stream.read_i32_le().await?;
+            if !(10..=MAX_PACKET_LENGTH).contains(&length) {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("invalid packet length {}", length),
+                ));
+            }
+            let id: i32 = self.stream.read_i32_le().await?;
+            let kind: i32 = self.stream.read_i32_le().await?;
+            let mut body: Vec<u8> = vec![0; length as usize - 8];
+            self.stream.read_exact(&mut body).await?;
+            body.truncate(body.len() - 2);
+            Ok((id, kind, String::from_utf8_lossy(&body).into_owned()))
+        })
+        .await
+        .map_err(|_| Error::new(ErrorKind::TimedOut, "timed out waiting for response"))?
+    }
+}
+
+/// Save the world and stop the server
+pub async fn save_and_stop(address: &str, password: &str) -> Result<()> {
+    let mut client: Client = Client::connect(address, password).await?;
+    let response: String = client.command("save-all flush").await?;
+    debug!(target: "lazymc-docker-proxy::rcon", "save-all flush: {}", response);
+    client.command_no_response("stop").await
+}
@@ -0,0 +1,49 @@
+use std::{collections::BTreeMap, fs, path::PathBuf, process::exit};
+
+use crate::{backend, entrypoint::config::Config};
+
+/// Print the lazymc config of each group, or write them to a directory, without starting or stopping any containers
+pub fn run(output: Option<PathBuf>) {
+    let mut configs: BTreeMap<String, Config> = BTreeMap::new();
+    let mut failed: bool = false;
+    let containers: Vec<backend::ContainerLabels> =
+        backend::runtime().block_on(backend::get_labels()).unwrap_or_else(|err| {
+            error!(target: "lazymc-docker-proxy::render", "Failed to get container labels: {}", err);
+            exit(1);
+        });
+    for container in containers {
+        match Config::from_container_labels(&container.name, container.labels) {
+            Ok(config) => {
+                configs.insert(config.group().into(), config);
+            }
+            Err(err) => {
+                error!(target: "lazymc-docker-proxy::render", "{}", err);
+                failed = true;
+            }
+        }
+    }
+
+    if configs.is_empty() && !failed {
+        warn!(target: "lazymc-docker-proxy::render", "No containers with the label lazymc.enabled=true found");
+    }
+
+    for config in configs.values() {
+        let toml: String = config.as_annotated_toml_string();
+        match &output {
+            Some(directory) => {
+                let path: PathBuf = directory.join(config.config_file());
+                if let Err(err) = fs::write(&path, toml) {
+                    error!(target: "lazymc-docker-proxy::render", "Failed to write {}: {}", path.display(), err);
+                    failed = true;
+                    continue;
+                }
+                info!(target: "lazymc-docker-proxy::render", "`rendered`: {}", path.display());
+            }
+            None => println!("# {}\n\n{}", config.config_file(), toml),
+        }
+    }
+
+    if failed {
+        exit(1);
+    }
+}
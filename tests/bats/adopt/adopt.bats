@@ -0,0 +1,44 @@
+#!/usr/bin/env bats
+
+load ../util.bash
+
+project="./tests/bats/adopt"
+
+@test "Adopt - Test lazymc adopts a running server on restart" {
+    # start the server container
+    start_container mc-adopt
+
+    # wait for the mincraft server to be ready
+    wait_for_log "mc-adopt" "RCON running on 0.0.0.0:25575" 300
+
+    # restart the lazymc container
+    restart_container lazymc-adopt
+
+    # reset the start timestamp
+    reset_timestamp
+
+    #
+    # The above steps ensures that the server is running when lazymc-docker-proxy starts
+    #
+
+    # wait for lazymc-docker-proxy to adopt the running server
+    wait_for_formatted_log "lazymc-adopt" "INFO" "lazymc-docker-proxy::entrypoint" "Adopting the running server of group mc"
+
+    # wait for lazymc process to start
+    wait_for_formatted_log "lazymc-adopt" "INFO" "lazymc-docker-proxy::entrypoint" "Starting lazymc process for group: mc..."
+
+    # wait for the server to be online without being restarted
+    wait_for_formatted_log "lazymc-adopt" "INFO" "mc::lazymc::monitor" "Server is now online" 300
+
+    # wait for the server to be idle
+    wait_for_formatted_log "lazymc-adopt" "INFO" "mc::lazymc::monitor" "Server has been idle, sleeping..." 120
+
+    # wait for the server to be stopped
+    wait_for_formatted_log "lazymc-adopt" "INFO" "mc::lazymc-docker-proxy::command" "Received SIGTERM, stopping server..."
+
+    # wait for the server to exit
+    wait_for_log "mc-adopt" "Thread RCON Listener stopped"
+
+    # wait for lazymc to sleep
+    wait_for_formatted_log "lazymc-adopt" "INFO" "mc::lazymc::monitor" "Server is now sleeping"
+}
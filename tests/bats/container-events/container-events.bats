@@ -0,0 +1,50 @@
+#!/usr/bin/env bats
+
+load ../util.bash
+
+project="./tests/bats/container-events"
+
+@test "Container events - Test lazymc follows containers created, renamed and removed after boot" {
+    # remove the server container
+    remove_container mc-container-events
+
+    # restart the lazymc container
+    restart_container lazymc-container-events
+
+    # reset the start timestamp
+    reset_timestamp
+
+    #
+    # The above steps ensures that lazymc-docker-proxy starts without a server container
+    #
+
+    # wait for lazymc-docker-proxy to wait for containers
+    wait_for_formatted_log "lazymc-container-events" "INFO" "lazymc-docker-proxy::entrypoint" "No containers with the label lazymc.enabled=true found, waiting for containers to be created..." 300
+
+    # create the server container
+    create_container mc-container-events
+
+    # wait for lazymc process to start for the created container
+    wait_for_formatted_log "lazymc-container-events" "INFO" "lazymc-docker-proxy::entrypoint" "Starting lazymc process for group: mc..."
+
+    reset_timestamp
+
+    # rename the server container
+    docker rename mc-container-events mc-container-events-renamed
+
+    # wait for lazymc to be restarted for the renamed container
+    wait_for_formatted_log "lazymc-container-events" "INFO" "lazymc-docker-proxy::entrypoint" "Stopping lazymc process for group: mc..."
+    wait_for_formatted_log "lazymc-container-events" "INFO" "lazymc-docker-proxy::entrypoint" "Starting lazymc process for group: mc..."
+
+    # rename the server container back
+    docker rename mc-container-events-renamed mc-container-events
+
+    reset_timestamp
+
+    # remove the server container
+    remove_container mc-container-events
+
+    # wait for lazymc to be stopped for the removed container
+    wait_for_formatted_log "lazymc-container-events" "INFO" "lazymc-docker-proxy::entrypoint" "Container for group mc was removed"
+    wait_for_formatted_log "lazymc-container-events" "INFO" "lazymc-docker-proxy::entrypoint" "Stopping lazymc process for group: mc..."
+}
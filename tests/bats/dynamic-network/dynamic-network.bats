@@ -0,0 +1,61 @@
+#!/usr/bin/env bats
+
+load ../util.bash
+
+project="./tests/bats/dynamic-network"
+
+@test "Dynamic network - Test lazymc follows the server to a new address" {
+    # stop the client container
+    stop_container mc-client-dynamic-network
+
+    # stop the server container
+    stop_container mc-dynamic-network
+
+    # stop the container which takes the address of the server
+    stop_container ip-thief-dynamic-network
+
+    # restart the lazymc container
+    restart_container lazymc-dynamic-network
+
+    # reset the start timestamp
+    reset_timestamp
+
+    #
+    # The above steps ensures that the containers are in a clean state
+    #
+
+    # wait for lazymc-docker-proxy to to be ready
+    wait_for_formatted_log "lazymc-dynamic-network" "INFO" "lazymc-docker-proxy::health" "Application is healthy." 300
+
+    # start the client container to wake the server
+    start_container mc-client-dynamic-network
+
+    # wait for the server to be online
+    wait_for_formatted_log "lazymc-dynamic-network" "INFO" "mc::lazymc::monitor" "Server is now online" 300
+
+    # disconnect the client
+    stop_container mc-client-dynamic-network
+
+    # wait for lazymc to sleep
+    wait_for_formatted_log "lazymc-dynamic-network" "INFO" "mc::lazymc::monitor" "Server is now sleeping" 300
+
+    # take the address of the stopped server, so it gets a different one when it starts again
+    start_container ip-thief-dynamic-network
+
+    reset_timestamp
+
+    # start the client container to wake the server again
+    start_container mc-client-dynamic-network
+
+    # wait for lazymc to start the server for the client
+    wait_for_formatted_log "lazymc-dynamic-network" "INFO" "mc::lazymc" "Starting server for 'test-bot'..." 300
+
+    # wait for lazymc-docker-proxy to notice the new address of the server
+    wait_for_formatted_log "lazymc-dynamic-network" "INFO" "lazymc-docker-proxy::entrypoint" "Address for group mc changed from" 300
+
+    # wait for the server to be online at its new address
+    wait_for_formatted_log "lazymc-dynamic-network" "INFO" "mc::lazymc::monitor" "Server is now online" 300
+
+    # disconnect the client
+    stop_container mc-client-dynamic-network
+}
@@ -0,0 +1,87 @@
+#!/usr/bin/env python3
+# A fake kubernetes API serving a single statefulset, which is scaled through its scale subresource.
+#
+# The test changes the statefulset with GET /fake/annotate/<key>/<value> and GET /fake/scale/<replicas>.
+
+import json
+import re
+from http.server import BaseHTTPRequestHandler, ThreadingHTTPServer
+
+NAMESPACE = "minecraft"
+# the address the pod of the statefulset gets while it runs, the service name resolves to this container instead
+POD_IP = "172.23.0.6"
+
+statefulset = {
+    "metadata": {
+        "name": "mc",
+        "annotations": {
+            "lazymc.enabled": "true",
+            "lazymc.group": "mc",
+            "lazymc.server.address": "kube-api-kubernetes:25565",
+            "lazymc.time.minimum_online_time": "30",
+            "lazymc.time.sleep_after": "5",
+        },
+    },
+    "spec": {"replicas": 0, "selector": {"matchLabels": {"app": "mc"}}},
+    "status": {"readyReplicas": 0},
+}
+
+
+def scale(replicas):
+    statefulset["spec"]["replicas"] = replicas
+    statefulset["status"]["readyReplicas"] = replicas
+
+
+class Handler(BaseHTTPRequestHandler):
+    def respond(self, code, body):
+        data = json.dumps(body).encode()
+        self.send_response(code)
+        self.send_header("Content-Type", "application/json")
+        self.send_header("Content-Length", str(len(data)))
+        self.end_headers()
+        self.wfile.write(data)
+
+    def not_found(self):
+        self.respond(404, {"kind": "Status", "message": "%s not found" % self.path, "code": 404})
+
+    def do_GET(self):
+        print("GET %s" % self.path, flush=True)
+        apps = "/apis/apps/v1/namespaces/%s" % NAMESPACE
+
+        if self.path == "/version":
+            self.respond(200, {"major": "1", "minor": "30"})
+        elif self.path == apps + "/deployments":
+            self.respond(200, {"items": []})
+        elif self.path == apps + "/statefulsets":
+            self.respond(200, {"items": [statefulset]})
+        elif self.path == apps + "/statefulsets/mc":
+            self.respond(200, statefulset)
+        elif self.path == "/api/v1/namespaces/%s/pods?labelSelector=app=mc" % NAMESPACE:
+            pods = [{"status": {"phase": "Running", "podIP": POD_IP}}] * statefulset["spec"]["replicas"]
+            self.respond(200, {"items": pods})
+        elif match := re.fullmatch(r"/fake/annotate/([^/]+)/([^/]+)", self.path):
+            statefulset["metadata"]["annotations"][match[1]] = match[2]
+            self.respond(200, statefulset)
+        elif match := re.fullmatch(r"/fake/scale/(\d+)", self.path):
+            scale(int(match[1]))
+            self.respond(200, statefulset)
+        else:
+            self.not_found()
+
+    def do_PATCH(self):
+        body = self.rfile.read(int(self.headers.get("Content-Length", 0))).decode()
+        print("PATCH %s %s" % (self.path, body), flush=True)
+
+        if self.path != "/apis/apps/v1/namespaces/%s/statefulsets/mc/scale" % NAMESPACE:
+            return self.not_found()
+        if self.headers.get("Content-Type") != "application/merge-patch+json":
+            return self.respond(415, {"kind": "Status", "message": "unsupported media type", "code": 415})
+
+        scale(json.loads(body)["spec"]["replicas"])
+        self.respond(200, {"spec": {"replicas": statefulset["spec"]["replicas"]}})
+
+    def log_message(self, format, *args):
+        pass
+
+
+ThreadingHTTPServer(("", 8001), Handler).serve_forever()
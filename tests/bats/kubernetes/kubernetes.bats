@@ -0,0 +1,76 @@
+#!/usr/bin/env bats
+
+load ../util.bash
+
+project="./tests/bats/kubernetes"
+
+# change the statefulset served by the fake kubernetes API
+kube_api() {
+    local path=$1
+
+    echo "Kubernetes API: $path" >&3
+    docker compose --project-directory $project exec kube-api-kubernetes wget -qO- "http://localhost:8001/fake/$path"
+}
+
+@test "Kubernetes - Test lazymc scales a statefulset and follows its pod" {
+    # stop the client container
+    stop_container mc-client-kubernetes
+
+    # reset the statefulset to 0 replicas
+    restart_container kube-api-kubernetes
+
+    # restart the lazymc container
+    restart_container lazymc-kubernetes
+
+    # reset the start timestamp
+    reset_timestamp
+
+    #
+    # The above steps ensures that the containers are in a clean state
+    #
+
+    # wait for lazymc-docker-proxy to to be ready
+    wait_for_formatted_log "lazymc-kubernetes" "INFO" "lazymc-docker-proxy::health" "Application is healthy." 300
+
+    # the statefulset has no pod, so its service name is used
+    wait_for_log "lazymc-kubernetes" "address = \"172.23.0.5:25565\""
+
+    # disable the statefulset and wait for the watch loop to notice
+    kube_api annotate/lazymc.enabled/false
+    wait_for_formatted_log "lazymc-kubernetes" "INFO" "lazymc-docker-proxy::entrypoint" "Container for group mc was removed"
+
+    reset_timestamp
+
+    # enable the statefulset again
+    kube_api annotate/lazymc.enabled/true
+    wait_for_formatted_log "lazymc-kubernetes" "INFO" "lazymc-docker-proxy::entrypoint" "Starting lazymc process for group: mc..."
+
+    # start the client container to wake the server
+    start_container mc-client-kubernetes
+
+    # wait for lazymc to start the server for the client
+    wait_for_formatted_log "lazymc-kubernetes" "INFO" "mc::lazymc" "Starting server for 'test-bot'..." 300
+
+    # wait for the statefulset to be scaled through its scale subresource
+    wait_for_formatted_log "lazymc-kubernetes" "INFO" "mc::lazymc-docker-proxy::kubernetes" "Scaling statefulset/mc to 1 replica\(s\)"
+    wait_for_log "kube-api-kubernetes" "PATCH /apis/apps/v1/namespaces/minecraft/statefulsets/mc/scale {\"spec\":{\"replicas\":1}}"
+    wait_for_formatted_log "lazymc-kubernetes" "INFO" "mc::lazymc-docker-proxy::kubernetes" "statefulset/mc is ready"
+
+    # wait for lazymc-docker-proxy to follow the server to its pod
+    wait_for_formatted_log "lazymc-kubernetes" "INFO" "lazymc-docker-proxy::entrypoint" "Address for group mc changed from 172.23.0.5:25565 to 172.23.0.6:25565"
+
+    # disconnect the client
+    stop_container mc-client-kubernetes
+
+    reset_timestamp
+
+    # scale the statefulset down, its pod goes away
+    kube_api scale/0
+
+    # give the watch loop time to notice
+    sleep 15
+
+    # the last pod IP is kept, so lazymc is not restarted with the address of the service
+    run docker compose --project-directory $project logs --since $start_timestamp --no-color lazymc-kubernetes
+    [[ "$output" != *"Address for group mc changed from 172.23.0.6:25565"* ]]
+}
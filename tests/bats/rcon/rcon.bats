@@ -0,0 +1,39 @@
+#!/usr/bin/env bats
+
+load ../util.bash
+
+project="./tests/bats/rcon"
+
+@test "RCON - Test lazymc saves and stops server through RCON when idle" {
+    # restart the lazymc container
+    restart_container lazymc-rcon
+
+    # reset the start timestamp
+    reset_timestamp
+
+    #
+    # The above steps ensures that the containers are in a clean state
+    #
+
+    # wait for lazymc process to start
+    wait_for_formatted_log "lazymc-rcon" "INFO" "lazymc-docker-proxy::entrypoint" "Starting lazymc process for group: mc..."
+
+    # wait for the server to be online
+    wait_for_formatted_log "lazymc-rcon" "INFO" "mc::lazymc::monitor" "Server is now online" 300
+
+    # wait for the mincraft server to be ready
+    wait_for_log "mc-rcon" "RCON running on 0.0.0.0:25575" 300
+
+    # wait for the server to be idle
+    wait_for_formatted_log "lazymc-rcon" "INFO" "mc::lazymc::monitor" "Server has been idle, sleeping..." 120
+
+    # wait for the server to be stopped through RCON
+    wait_for_formatted_log "lazymc-rcon" "INFO" "mc::lazymc-docker-proxy::docker" "Saving and stopping container mc-rcon using RCON"
+
+    # wait for the server to save and exit on its own
+    wait_for_log "mc-rcon" "Stopping the server"
+    wait_for_formatted_log "lazymc-rcon" "INFO" "mc::lazymc-docker-proxy::docker" "Container mc-rcon stopped gracefully using RCON"
+
+    # wait for lazymc to sleep
+    wait_for_formatted_log "lazymc-rcon" "INFO" "mc::lazymc::monitor" "Server is now sleeping"
+}
@@ -42,9 +42,15 @@ project="./tests/bats/static-network"
     # wait for the mincraft server to be ready
     wait_for_log "mc-static-network" "RCON running on 0.0.0.0:25575" 300
 
+    # wait for lazymc-docker-proxy to see the client join the server
+    wait_for_formatted_log "lazymc-static-network" "INFO" "lazymc-docker-proxy::entrypoint" "test-bot joined the server for group mc" 120
+
     # disconnect the client
     stop_container mc-client-static-network
 
+    # wait for lazymc-docker-proxy to see the client leave the server
+    wait_for_formatted_log "lazymc-static-network" "INFO" "lazymc-docker-proxy::entrypoint" "test-bot left the server for group mc"
+
     # wait for the server to be idle
     wait_for_formatted_log "lazymc-static-network" "INFO" "mc::lazymc::monitor" "Server has been idle, sleeping..." 120
 
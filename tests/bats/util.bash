@@ -89,3 +89,17 @@ restart_container() {
     echo "Restarting container: $container" >&3
     docker compose --project-directory $project restart $container
 }
+
+create_container() {
+    local container=$1
+
+    echo "Creating container: $container" >&3
+    docker compose --project-directory $project create $container
+}
+
+remove_container() {
+    local container=$1
+
+    echo "Removing container: $container" >&3
+    docker compose --project-directory $project rm --stop --force $container
+}